@@ -0,0 +1,103 @@
+// cargo bench: the solvers at several sizes, on any host (std::time::Instant only)
+use economy3::bench::{bench, Stats};
+use economy3::economy::Economy;
+use economy3::gauss_newton::{gauss_newton_bounded, Bounds, LineSearch};
+use economy3::recipe_graph::RecipeGraph;
+use economy3::rs_leastsquare::{least_squares, solve_auto};
+use economy3::supply_chain::Generator;
+use std::time::Duration;
+
+const WARM_UP: Duration = Duration::from_millis(300);
+const REPETITIONS: u32 = 30;
+
+// recipes of a generated economy, with at least as many labors as goods
+fn chain(goods: usize, seed: u64) -> nalgebra::DMatrix<f32> {
+    Generator {
+        goods,
+        ..Generator::default()
+    }
+    .generate(seed)
+    .recipe_matrix()
+}
+
+// my_neg_inverse in src/bin/pseudo_inverse_speed.rs: building the recipe graph and
+// propagating the labor effort over it
+fn neg_inverse(goods: usize) -> Stats {
+    let x = chain(goods, 1);
+    let labors = x.ncols();
+    bench(
+        &format!("my_neg_inverse {}x{}", goods, labors),
+        WARM_UP,
+        REPETITIONS,
+        || RecipeGraph::new(goods, labors, |r, c| x[(r, c)], |_| 1.0_f32, 0.001).propagate(labors),
+    )
+}
+
+fn gauss_newton<const M: usize, const N: usize>() -> Stats {
+    let x = chain(M.max(N), 2);
+    let equation = nalgebra::SMatrix::<f32, M, N>::from_fn(|r, c| x[(r, c)]);
+    let bias = nalgebra::SMatrix::<f32, M, 1>::repeat(1.0);
+    let x0 = nalgebra::SMatrix::<f32, N, 1>::repeat(1.0);
+    let bounds = Bounds {
+        lower: nalgebra::SMatrix::zeros(),
+        upper: nalgebra::SMatrix::repeat(1e6),
+    };
+    bench(
+        &format!("gauss_newton_bounded {}x{}", M, N),
+        WARM_UP,
+        REPETITIONS,
+        || gauss_newton_bounded(&equation, &bias, &x0, &bounds, LineSearch::Halving),
+    )
+}
+
+fn least_squares_dense(m: usize, n: usize) -> Stats {
+    let x = chain(m, 3).map(f64::from);
+    let x = x.columns(0, n).into_owned();
+    let y = nalgebra::DMatrix::<f64>::repeat(m, 1, 1.0);
+    bench(
+        &format!("least_squares {}x{}", m, n),
+        WARM_UP,
+        REPETITIONS,
+        || least_squares(&x, &y),
+    )
+}
+
+// the whole allocation problem of generated economies, beyond the 51x44 of the game
+fn generated(goods: usize) -> Stats {
+    let chain = Generator {
+        goods,
+        ..Generator::default()
+    }
+    .generate(4);
+    let x = chain.recipe_matrix().map(f64::from);
+    let y = nalgebra::DMatrix::<f64>::repeat(goods, 1, 1.0);
+    bench(
+        &format!("solve_auto generated {}x{}", goods, chain.labors.len()),
+        WARM_UP,
+        REPETITIONS,
+        || solve_auto(&x, &y, 1e-9),
+    )
+}
+
+fn tick<T: economy3::gauss_newton::Scalar>(name: &str) -> Stats {
+    let mut economy = Economy::<T>::new(nalgebra::convert(100.0));
+    economy.verbose = false;
+    bench(name, WARM_UP, REPETITIONS, || economy.tick())
+}
+
+fn main() {
+    for goods in [51, 200, 1000] {
+        println!("{}", neg_inverse(goods));
+    }
+    println!("{}", gauss_newton::<4, 5>());
+    println!("{}", gauss_newton::<16, 16>());
+    println!("{}", gauss_newton::<51, 44>());
+    for (m, n) in [(20, 16), (100, 80), (400, 300)] {
+        println!("{}", least_squares_dense(m, n));
+    }
+    for goods in [51, 200, 400] {
+        println!("{}", generated(goods));
+    }
+    println!("{}", tick::<f32>("Economy::tick f32"));
+    println!("{}", tick::<f64>("Economy::tick f64"));
+}
@@ -0,0 +1,671 @@
+// Heap backed matrices with the API of SMatrix and SVector, for economies too big for the stack
+use crate::{factor, SMatrix, SVector};
+use alloc::vec::Vec;
+use core::{
+    fmt::{Debug, Formatter, Write},
+    iter::Sum,
+    ops::{Add, AddAssign, Div, Index, IndexMut, Mul, Neg, Sub},
+};
+#[cfg(any(feature = "std", feature = "libm"))]
+use num_traits::float::Float;
+use num_traits::float::FloatCore;
+
+/// Matrix with dynamic size, row major
+#[derive(Clone, PartialEq)]
+pub struct DMatrix<T> {
+    nrows: usize,
+    ncols: usize,
+    buffer: Vec<T>,
+}
+
+/// Vector with dynamic size
+#[derive(Clone, PartialEq)]
+pub struct DVector<T> {
+    buffer: Vec<T>,
+}
+
+/// Column of a dynamic matrix
+pub struct DColumnRef<'a, T> {
+    column: usize,
+    matrix: &'a DMatrix<T>,
+}
+
+impl<T> DMatrix<T> {
+    pub fn nrows(&self) -> usize {
+        self.nrows
+    }
+
+    pub fn ncols(&self) -> usize {
+        self.ncols
+    }
+
+    pub fn shape(&self) -> (usize, usize) {
+        (self.nrows, self.ncols)
+    }
+}
+
+impl<T> DVector<T> {
+    pub fn len(&self) -> usize {
+        self.buffer.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.buffer.is_empty()
+    }
+}
+
+impl<T: FloatCore + Default + Sum + AddAssign> DMatrix<T> {
+    pub fn zeros(nrows: usize, ncols: usize) -> Self {
+        DMatrix {
+            nrows,
+            ncols,
+            buffer: alloc::vec![T::default(); nrows * ncols],
+        }
+    }
+
+    pub fn from_fn<F: Fn(usize, usize) -> T>(nrows: usize, ncols: usize, f: F) -> Self {
+        DMatrix {
+            nrows,
+            ncols,
+            buffer: (0..nrows * ncols)
+                .map(|i| f(i / ncols, i % ncols))
+                .collect(),
+        }
+    }
+
+    /// Ones on the diagonal, also for non square matrices
+    pub fn identity(nrows: usize, ncols: usize) -> Self {
+        Self::from_fn(
+            nrows,
+            ncols,
+            |r, c| if r == c { T::one() } else { T::zero() },
+        )
+    }
+
+    pub fn transpose(&self) -> DMatrix<T> {
+        DMatrix::from_fn(self.ncols, self.nrows, |r, c| self[(c, r)])
+    }
+
+    pub fn row(&self, r: usize) -> &[T] {
+        &self.buffer[r * self.ncols..(r + 1) * self.ncols]
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = &[T]> {
+        // every row, also the empty ones of a matrix without columns
+        (0..self.nrows).map(move |r| self.row(r))
+    }
+
+    pub fn column(&self, column: usize) -> DColumnRef<'_, T> {
+        DColumnRef {
+            column,
+            matrix: self,
+        }
+    }
+
+    pub fn norm_squared(&self) -> T {
+        self.buffer.iter().map(|x| *x * *x).sum()
+    }
+
+    /// LU decomposition with partial pivoting, panics unless square
+    pub fn lu(&self) -> DLU<T> {
+        assert_eq!(self.nrows, self.ncols, "LU of a non square matrix");
+        let mut lu = self.clone();
+        let mut permutation = alloc::vec![0; self.nrows];
+        let odd = factor::lu(&mut lu.buffer, self.nrows, &mut permutation);
+        DLU {
+            lu,
+            permutation,
+            odd,
+        }
+    }
+
+    /// None if singular, panics unless square
+    pub fn try_inverse(&self) -> Option<Self> {
+        self.lu().try_inverse()
+    }
+}
+
+#[cfg(any(feature = "std", feature = "libm"))]
+impl<T: FloatCore + Float + Default + Sum + AddAssign> DMatrix<T> {
+    /// Lower triangular L with L Lᵀ = self, None unless symmetric positive definite
+    pub fn cholesky(&self) -> Option<DCholesky<T>> {
+        assert_eq!(self.nrows, self.ncols, "Cholesky of a non square matrix");
+        let mut l = Self::zeros(self.nrows, self.ncols);
+        factor::cholesky(&self.buffer, &mut l.buffer, self.nrows).then_some(DCholesky { l })
+    }
+}
+
+impl<T: FloatCore + Default + Sum + AddAssign> DVector<T> {
+    pub fn zeros(nrows: usize) -> Self {
+        DVector {
+            buffer: alloc::vec![T::default(); nrows],
+        }
+    }
+
+    pub fn from_fn<F: Fn(usize, usize) -> T>(nrows: usize, f: F) -> Self {
+        DVector {
+            buffer: (0..nrows).map(|r| f(r, 0)).collect(),
+        }
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = &T> {
+        self.buffer.iter()
+    }
+
+    pub fn zip_map<F: Fn(T, T) -> T>(&self, b: &Self, f: F) -> Self {
+        assert_eq!(self.len(), b.len(), "vectors of different length");
+        DVector {
+            buffer: self.iter().zip(b.iter()).map(|(a, b)| f(*a, *b)).collect(),
+        }
+    }
+
+    pub fn component_mul(&self, b: &Self) -> Self {
+        self.zip_map(b, |a, b| a * b)
+    }
+
+    pub fn norm_squared(&self) -> T {
+        self.buffer.iter().map(|x| *x * *x).sum()
+    }
+
+    pub fn dot(&self, b: &Self) -> T {
+        assert_eq!(self.len(), b.len(), "vectors of different length");
+        self.iter().zip(b.iter()).map(|(a, b)| *a * *b).sum()
+    }
+
+    /// Row vector
+    pub fn transpose(&self) -> DMatrix<T> {
+        DMatrix {
+            nrows: 1,
+            ncols: self.len(),
+            buffer: self.buffer.clone(),
+        }
+    }
+}
+
+/// P A = L U, see `SMatrix::lu`
+pub struct DLU<T> {
+    lu: DMatrix<T>,
+    permutation: Vec<usize>,
+    odd: bool,
+}
+
+impl<T: FloatCore + Default + Sum + AddAssign> DLU<T> {
+    pub fn is_invertible(&self) -> bool {
+        (0..self.lu.nrows).all(|i| self.lu[(i, i)] != T::zero() && !self.lu[(i, i)].is_nan())
+    }
+
+    pub fn determinant(&self) -> T {
+        let det = (0..self.lu.nrows).fold(T::one(), |det, i| det * self.lu[(i, i)]);
+        if self.odd {
+            -det
+        } else {
+            det
+        }
+    }
+
+    /// Solves A x = b, None if A is singular
+    pub fn solve(&self, b: &DVector<T>) -> Option<DVector<T>> {
+        assert_eq!(b.len(), self.lu.nrows, "wrong right hand side");
+        if !self.is_invertible() {
+            return None;
+        }
+        let mut x = DVector::zeros(b.len());
+        factor::lu_solve(
+            &self.lu.buffer,
+            self.lu.nrows,
+            &self.permutation,
+            &b.buffer,
+            &mut x.buffer,
+        );
+        Some(x)
+    }
+
+    pub fn try_inverse(&self) -> Option<DMatrix<T>> {
+        let n = self.lu.nrows;
+        let mut inverse = DMatrix::zeros(n, n);
+        for c in 0..n {
+            let column = self.solve(&DVector::from_fn(n, |r, _| {
+                if r == c {
+                    T::one()
+                } else {
+                    T::zero()
+                }
+            }))?;
+            for r in 0..n {
+                inverse[(r, c)] = column.buffer[r];
+            }
+        }
+        Some(inverse)
+    }
+}
+
+/// A = L Lᵀ, see `DMatrix::cholesky`
+pub struct DCholesky<T> {
+    l: DMatrix<T>,
+}
+
+impl<T: FloatCore + Default + Sum + AddAssign> DCholesky<T> {
+    pub fn l(&self) -> &DMatrix<T> {
+        &self.l
+    }
+
+    /// Solves A x = b
+    pub fn solve(&self, b: &DVector<T>) -> DVector<T> {
+        assert_eq!(b.len(), self.l.nrows, "wrong right hand side");
+        let mut x = b.clone();
+        factor::cholesky_solve(&self.l.buffer, self.l.nrows, &mut x.buffer);
+        x
+    }
+
+    pub fn inverse(&self) -> DMatrix<T> {
+        let n = self.l.nrows;
+        let mut inverse = DMatrix::zeros(n, n);
+        for c in 0..n {
+            let column = self.solve(&DVector::from_fn(n, |r, _| {
+                if r == c {
+                    T::one()
+                } else {
+                    T::zero()
+                }
+            }));
+            for r in 0..n {
+                inverse[(r, c)] = column.buffer[r];
+            }
+        }
+        inverse
+    }
+}
+
+impl<T: FloatCore + Default + Sum + AddAssign> Mul<&DVector<T>> for &DMatrix<T> {
+    type Output = DVector<T>;
+
+    fn mul(self, rhs: &DVector<T>) -> Self::Output {
+        assert_eq!(self.ncols, rhs.len(), "matrix and vector don't match");
+        DVector {
+            buffer: self
+                .iter()
+                .map(|row| row.iter().zip(rhs.iter()).map(|(a, b)| *a * *b).sum())
+                .collect(),
+        }
+    }
+}
+
+impl<T: FloatCore + Default + Sum + AddAssign> Mul<DVector<T>> for DMatrix<T> {
+    type Output = DVector<T>;
+
+    fn mul(self, rhs: DVector<T>) -> Self::Output {
+        &self * &rhs
+    }
+}
+
+impl<T: FloatCore + Default + Sum + AddAssign> Mul<&DMatrix<T>> for &DMatrix<T> {
+    type Output = DMatrix<T>;
+
+    fn mul(self, rhs: &DMatrix<T>) -> Self::Output {
+        assert_eq!(self.ncols, rhs.nrows, "matrices don't match");
+        let mut res = DMatrix::zeros(self.nrows, rhs.ncols);
+        for r in 0..self.nrows {
+            for c in 0..self.ncols {
+                let a = self[(r, c)];
+                for k in 0..rhs.ncols {
+                    res[(r, k)] += a * rhs[(c, k)];
+                }
+            }
+        }
+        res
+    }
+}
+
+impl<T: FloatCore + Default + Sum + AddAssign> Mul<DMatrix<T>> for DMatrix<T> {
+    type Output = DMatrix<T>;
+
+    fn mul(self, rhs: DMatrix<T>) -> Self::Output {
+        &self * &rhs
+    }
+}
+
+// scalar multiplication
+impl<T: FloatCore> Mul<T> for DVector<T> {
+    type Output = DVector<T>;
+
+    fn mul(mut self, rhs: T) -> Self::Output {
+        self.buffer.iter_mut().for_each(|x| *x = *x * rhs);
+        self
+    }
+}
+
+impl<T: FloatCore> Mul<T> for DMatrix<T> {
+    type Output = DMatrix<T>;
+
+    fn mul(mut self, rhs: T) -> Self::Output {
+        self.buffer.iter_mut().for_each(|x| *x = *x * rhs);
+        self
+    }
+}
+
+impl<T: FloatCore> Div<T> for DVector<T> {
+    type Output = DVector<T>;
+
+    fn div(self, rhs: T) -> Self::Output {
+        self * (T::one() / rhs)
+    }
+}
+
+// the scalar on the left only works for concrete types
+macro_rules! scalar_mul {
+    ($($t:ty),*) => {$(
+        impl Mul<DVector<$t>> for $t {
+            type Output = DVector<$t>;
+
+            fn mul(self, rhs: DVector<$t>) -> Self::Output {
+                rhs * self
+            }
+        }
+
+        impl Mul<DMatrix<$t>> for $t {
+            type Output = DMatrix<$t>;
+
+            fn mul(self, rhs: DMatrix<$t>) -> Self::Output {
+                rhs * self
+            }
+        }
+    )*};
+}
+
+scalar_mul!(f32, f64);
+
+// element wise operations of equally sized buffers
+fn zip<T: FloatCore>(a: &[T], b: &[T], f: impl Fn(T, T) -> T) -> Vec<T> {
+    assert_eq!(a.len(), b.len(), "sizes don't match");
+    a.iter().zip(b.iter()).map(|(a, b)| f(*a, *b)).collect()
+}
+
+impl<T: FloatCore> Add<DVector<T>> for DVector<T> {
+    type Output = DVector<T>;
+
+    fn add(self, rhs: DVector<T>) -> Self::Output {
+        &self + &rhs
+    }
+}
+
+impl<T: FloatCore> Add<&DVector<T>> for &DVector<T> {
+    type Output = DVector<T>;
+
+    fn add(self, rhs: &DVector<T>) -> Self::Output {
+        DVector {
+            buffer: zip(&self.buffer, &rhs.buffer, |a, b| a + b),
+        }
+    }
+}
+
+impl<T: FloatCore> Sub<&DVector<T>> for &DVector<T> {
+    type Output = DVector<T>;
+
+    fn sub(self, rhs: &DVector<T>) -> Self::Output {
+        DVector {
+            buffer: zip(&self.buffer, &rhs.buffer, |a, b| a - b),
+        }
+    }
+}
+
+impl<T: FloatCore> Add<&DMatrix<T>> for &DMatrix<T> {
+    type Output = DMatrix<T>;
+
+    fn add(self, rhs: &DMatrix<T>) -> Self::Output {
+        assert_eq!(self.shape(), rhs.shape(), "matrices don't match");
+        DMatrix {
+            nrows: self.nrows,
+            ncols: self.ncols,
+            buffer: zip(&self.buffer, &rhs.buffer, |a, b| a + b),
+        }
+    }
+}
+
+impl<T: FloatCore> Sub<&DMatrix<T>> for &DMatrix<T> {
+    type Output = DMatrix<T>;
+
+    fn sub(self, rhs: &DMatrix<T>) -> Self::Output {
+        assert_eq!(self.shape(), rhs.shape(), "matrices don't match");
+        DMatrix {
+            nrows: self.nrows,
+            ncols: self.ncols,
+            buffer: zip(&self.buffer, &rhs.buffer, |a, b| a - b),
+        }
+    }
+}
+
+impl<T> Index<(usize, usize)> for DMatrix<T> {
+    type Output = T;
+
+    fn index(&self, index: (usize, usize)) -> &Self::Output {
+        assert!(
+            index.0 < self.nrows && index.1 < self.ncols,
+            "index out of bounds"
+        );
+        &self.buffer[index.0 * self.ncols + index.1]
+    }
+}
+
+impl<T> IndexMut<(usize, usize)> for DMatrix<T> {
+    fn index_mut(&mut self, index: (usize, usize)) -> &mut Self::Output {
+        assert!(
+            index.0 < self.nrows && index.1 < self.ncols,
+            "index out of bounds"
+        );
+        &mut self.buffer[index.0 * self.ncols + index.1]
+    }
+}
+
+impl<T> Index<(usize, usize)> for DVector<T> {
+    type Output = T;
+
+    fn index(&self, index: (usize, usize)) -> &Self::Output {
+        &self.buffer[index.0]
+    }
+}
+
+impl<T> IndexMut<(usize, usize)> for DVector<T> {
+    fn index_mut(&mut self, index: (usize, usize)) -> &mut Self::Output {
+        &mut self.buffer[index.0]
+    }
+}
+
+impl<T: FloatCore> Neg for DVector<T> {
+    type Output = Self;
+
+    fn neg(mut self) -> Self::Output {
+        self.buffer.iter_mut().for_each(|x| *x = -*x);
+        self
+    }
+}
+
+impl<T: FloatCore> Neg for DMatrix<T> {
+    type Output = Self;
+
+    fn neg(mut self) -> Self::Output {
+        self.buffer.iter_mut().for_each(|x| *x = -*x);
+        self
+    }
+}
+
+impl<T: FloatCore + Default + Sum + AddAssign + Debug> Debug for DMatrix<T> {
+    fn fmt(&self, fmt: &mut Formatter<'_>) -> Result<(), core::fmt::Error> {
+        fmt.write_str("[\n")?;
+        for r in 0..self.nrows {
+            for c in self.row(r) {
+                c.fmt(fmt)?;
+                fmt.write_char(' ')?;
+            }
+            fmt.write_char('\n')?;
+        }
+        fmt.write_char(']')?;
+        Ok(())
+    }
+}
+
+impl<T: FloatCore + Default + Sum + AddAssign + Debug> Debug for DVector<T> {
+    fn fmt(&self, fmt: &mut Formatter<'_>) -> Result<(), core::fmt::Error> {
+        fmt.write_char('[')?;
+        for r in self.iter() {
+            r.fmt(fmt)?;
+            fmt.write_char(' ')?;
+        }
+        fmt.write_char(']')?;
+        Ok(())
+    }
+}
+
+impl<'a, T> DColumnRef<'a, T> {
+    pub fn iter(&self) -> impl Iterator<Item = &'a T> {
+        let matrix = self.matrix;
+        let column = self.column;
+        (0..matrix.nrows).map(move |r| &matrix.buffer[r * matrix.ncols + column])
+    }
+}
+
+// conversions between the static and the heap matrices
+impl<T: FloatCore + Default + Sum + AddAssign, const R: usize, const C: usize>
+    From<&SMatrix<T, R, C>> for DMatrix<T>
+{
+    fn from(m: &SMatrix<T, R, C>) -> Self {
+        DMatrix::from_fn(R, C, |r, c| m[(r, c)])
+    }
+}
+
+impl<T: FloatCore + Default + Sum + AddAssign, const R: usize> From<&SVector<T, R>> for DVector<T> {
+    fn from(v: &SVector<T, R>) -> Self {
+        DVector::from_fn(R, |r, _| v[(r, 0)])
+    }
+}
+
+/// Fails with the shape of the matrix unless it is R x C
+impl<T: FloatCore + Default + Sum + AddAssign, const R: usize, const C: usize> TryFrom<&DMatrix<T>>
+    for SMatrix<T, R, C>
+{
+    type Error = (usize, usize);
+
+    fn try_from(m: &DMatrix<T>) -> Result<Self, Self::Error> {
+        if m.shape() != (R, C) {
+            return Err(m.shape());
+        }
+        Ok(SMatrix::from_fn(|r, c| m[(r, c)]))
+    }
+}
+
+/// Fails with the length of the vector unless it is R
+impl<T: FloatCore + Default + Sum + AddAssign, const R: usize> TryFrom<&DVector<T>>
+    for SVector<T, R>
+{
+    type Error = usize;
+
+    fn try_from(v: &DVector<T>) -> Result<Self, Self::Error> {
+        if v.len() != R {
+            return Err(v.len());
+        }
+        Ok(SVector::from_fn(|r, _| v[(r, 0)]))
+    }
+}
+
+#[cfg(feature = "std")]
+impl<T: FloatCore> From<DVector<T>> for Vec<T> {
+    fn from(v: DVector<T>) -> Self {
+        v.buffer
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{DMatrix, DVector};
+    use crate::{SMatrix, SVector};
+
+    #[test]
+    fn same_as_static() {
+        let a = SMatrix::<f64, 3, 3>::from_fn(|r, c| {
+            [[0.0, 2.0, 1.0], [1.0, 1.0, 0.0], [2.0, 1.0, 3.0]][r][c]
+        });
+        let b = SVector::<f64, 3>::from_fn(|r, _| [3.0, 2.0, 6.0][r]);
+        let da = DMatrix::from(&a);
+        let db = DVector::from(&b);
+        assert_eq!(da.shape(), (3, 3));
+        assert_eq!(SMatrix::try_from(&da.transpose()), Ok(a.transpose()));
+        assert_eq!(SMatrix::try_from(&(&da * &da)), Ok(&a * &a));
+        assert_eq!(SVector::try_from(&(&da * &db)), Ok(&a * &b));
+        assert_eq!(SMatrix::try_from(&(da.clone() * 2.0)), Ok(2.0 * a.clone()));
+        assert_eq!(
+            SMatrix::<f64, 3, 3>::try_from(&(&da - &da.clone())),
+            Ok(SMatrix::zeros())
+        );
+        assert_eq!(SVector::try_from(&-(&db + &db)), Ok(-(&b + &b)));
+        assert_eq!(db.dot(&db), b.dot(&b));
+        assert_eq!(da.row(1), a.row(1));
+        assert!(da.column(2).iter().eq(a.column(2).iter()));
+        assert_eq!(
+            SMatrix::<f64, 3, 3>::try_from(&DMatrix::identity(3, 3)),
+            Ok(SMatrix::identity())
+        );
+        assert_eq!(SMatrix::<f64, 2, 3>::try_from(&da), Err((3, 3)));
+        assert_eq!(SVector::<f64, 2>::try_from(&db), Err(3));
+
+        let lu = da.lu();
+        assert_eq!(lu.determinant(), a.lu().determinant());
+        assert_eq!(
+            SVector::try_from(&lu.solve(&db).unwrap()),
+            Ok(a.lu().solve(&b).unwrap())
+        );
+        assert_eq!(
+            SMatrix::try_from(&da.try_inverse().unwrap()),
+            Ok(a.try_inverse().unwrap())
+        );
+        assert!(DMatrix::<f64>::zeros(2, 2).try_inverse().is_none());
+        let mut broken = da.clone();
+        broken[(1, 0)] = f64::NAN;
+        assert!(broken.try_inverse().is_none());
+    }
+
+    #[test]
+    fn without_columns() {
+        let x = DMatrix::<f64>::zeros(3, 0);
+        assert_eq!(x.iter().count(), 3);
+        let y = &x * &DVector::zeros(0);
+        assert_eq!(y.len(), 3);
+        assert!(y.iter().all(|&v| v == 0.0));
+        assert_eq!((&x * &DMatrix::zeros(0, 2)).shape(), (3, 2));
+        assert_eq!(x.transpose().iter().count(), 0);
+    }
+
+    #[test]
+    #[cfg(any(feature = "std", feature = "libm"))]
+    fn cholesky() {
+        let a = SMatrix::<f64, 3, 3>::from_fn(|r, c| {
+            [[0.0, 2.0, 1.0], [1.0, 1.0, 0.0], [2.0, 1.0, 3.0]][r][c]
+        });
+        let spd = &a.transpose() * &a;
+        let dspd = DMatrix::from(&spd);
+        let cholesky = dspd.cholesky().unwrap();
+        assert_eq!(
+            SMatrix::try_from(cholesky.l()),
+            Ok(spd.cholesky().unwrap().l().clone())
+        );
+        let b = DVector::from_fn(3, |r, _| r as f64);
+        assert!((&(&dspd * &cholesky.solve(&b)) - &b).norm_squared() < 1e-20);
+        assert!(DMatrix::<f64>::zeros(2, 2).cholesky().is_none());
+    }
+
+    // a supply chain far beyond what fits on the stack as [[f32; 3000]; 3000]
+    #[test]
+    fn big() {
+        let n = 3000;
+        let x = DMatrix::<f32>::from_fn(n, n, |r, c| {
+            if r == c {
+                1.0
+            } else if c == r + 1 {
+                -0.5
+            } else {
+                0.0
+            }
+        });
+        let y = &x * &DVector::from_fn(n, |_, _| 1.0);
+        assert_eq!(y[(n - 1, 0)], 1.0);
+        assert!(y.iter().take(n - 1).all(|&v| v == 0.5));
+    }
+}
@@ -0,0 +1,95 @@
+// LU and Cholesky on row major n x n slices, shared by the static and the heap matrices
+#[cfg(any(feature = "std", feature = "libm"))]
+use num_traits::float::Float;
+use num_traits::float::FloatCore;
+
+/// LU decomposition with partial pivoting of `a` in place, row i of P A is row
+/// `permutation[i]` of A. Returns whether the number of row swaps is odd.
+pub fn lu<T: FloatCore>(a: &mut [T], n: usize, permutation: &mut [usize]) -> bool {
+    for (i, p) in permutation.iter_mut().enumerate() {
+        *p = i;
+    }
+    let mut swaps = 0;
+    for k in 0..n {
+        // NaN wins, a NaN pivot marks the matrix as singular
+        let pivot = (k..n)
+            .max_by(|&r1, &r2| {
+                let (a1, a2) = (a[r1 * n + k].abs(), a[r2 * n + k].abs());
+                a1.partial_cmp(&a2)
+                    .unwrap_or_else(|| a1.is_nan().cmp(&a2.is_nan()))
+            })
+            .unwrap();
+        if pivot != k {
+            for c in 0..n {
+                a.swap(pivot * n + c, k * n + c);
+            }
+            permutation.swap(pivot, k);
+            swaps += 1;
+        }
+        if a[k * n + k] == T::zero() {
+            continue;
+        }
+        for r in k + 1..n {
+            let factor = a[r * n + k] / a[k * n + k];
+            a[r * n + k] = factor;
+            for c in k + 1..n {
+                a[r * n + c] = a[r * n + c] - factor * a[k * n + c];
+            }
+        }
+    }
+    swaps % 2 == 1
+}
+
+/// Solves L U x = P b for the result of `lu`, U must be regular
+pub fn lu_solve<T: FloatCore>(lu: &[T], n: usize, permutation: &[usize], b: &[T], x: &mut [T]) {
+    for r in 0..n {
+        x[r] = b[permutation[r]];
+        for c in 0..r {
+            x[r] = x[r] - lu[r * n + c] * x[c];
+        }
+    }
+    for r in (0..n).rev() {
+        for c in r + 1..n {
+            x[r] = x[r] - lu[r * n + c] * x[c];
+        }
+        x[r] = x[r] / lu[r * n + r];
+    }
+}
+
+/// Lower triangular `l` with L Lᵀ = A, false unless A is symmetric positive definite
+#[cfg(any(feature = "std", feature = "libm"))]
+pub fn cholesky<T: FloatCore + Float>(a: &[T], l: &mut [T], n: usize) -> bool {
+    for c in 0..n {
+        for r in c..n {
+            let mut sum = a[r * n + c];
+            for k in 0..c {
+                sum = sum - l[r * n + k] * l[c * n + k];
+            }
+            if r == c {
+                if sum <= T::zero() || FloatCore::is_nan(sum) {
+                    return false;
+                }
+                l[c * n + c] = Float::sqrt(sum);
+            } else {
+                l[r * n + c] = sum / l[c * n + c];
+            }
+        }
+    }
+    true
+}
+
+/// Solves L Lᵀ x = b in place
+pub fn cholesky_solve<T: FloatCore>(l: &[T], n: usize, x: &mut [T]) {
+    for r in 0..n {
+        for c in 0..r {
+            x[r] = x[r] - l[r * n + c] * x[c];
+        }
+        x[r] = x[r] / l[r * n + r];
+    }
+    for r in (0..n).rev() {
+        for c in r + 1..n {
+            x[r] = x[r] - l[c * n + r] * x[c];
+        }
+        x[r] = x[r] / l[r * n + r];
+    }
+}
@@ -0,0 +1,753 @@
+//! Very small replacement for nalgebra, embeddable anywhere: only `core` is needed. Cholesky
+//! needs a square root, from std (feature `std`, the default) or from `libm`. The heap backed
+//! `DMatrix` and `DVector` need `alloc` (part of `std`).
+#![no_std]
+
+#[cfg(feature = "alloc")]
+extern crate alloc;
+#[cfg(any(test, feature = "std"))]
+extern crate std;
+
+#[cfg(feature = "alloc")]
+mod dynamic;
+mod factor;
+
+#[cfg(feature = "alloc")]
+pub use dynamic::{DCholesky, DMatrix, DVector, DLU};
+
+use core::{
+    fmt::{Debug, Formatter, Write},
+    iter::Sum,
+    ops::{Add, AddAssign, Div, Index, IndexMut, Mul, Neg, Sub},
+};
+#[cfg(any(feature = "std", feature = "libm"))]
+use num_traits::float::Float;
+use num_traits::float::FloatCore;
+
+/// Matrix with static size
+#[derive(Clone, PartialEq)]
+pub struct SMatrix<T: FloatCore, const R: usize, const C: usize> {
+    buffer: [[T; C]; R],
+}
+
+/// Vector with static size
+#[derive(Clone, PartialEq)]
+pub struct SVector<T, const R: usize> {
+    buffer: [T; R],
+}
+
+/// Column of a matrix
+pub struct ColumnRef<'a, T: FloatCore, const R: usize, const C: usize> {
+    column: usize,
+    matrix: &'a SMatrix<T, R, C>,
+}
+
+/// Column iterator into matrix
+pub struct ColumnRefIter<'a, T: FloatCore, const R: usize, const C: usize> {
+    column: usize,
+    row: usize,
+    matrix: &'a SMatrix<T, R, C>,
+}
+
+impl<T: FloatCore + Default + Sum + AddAssign, const R: usize, const C: usize> SMatrix<T, R, C> {
+    pub fn zeros() -> Self {
+        SMatrix {
+            buffer: [[T::default(); C]; R],
+        }
+    }
+
+    pub fn from_fn<F: Fn(usize, usize) -> T>(f: F) -> Self {
+        let mut res = Self::zeros();
+        for r in 0..R {
+            for c in 0..C {
+                res.buffer[r][c] = f(r, c);
+            }
+        }
+        res
+    }
+
+    /// Ones on the diagonal, also for non square matrices
+    pub fn identity() -> Self {
+        Self::from_fn(|r, c| if r == c { T::one() } else { T::zero() })
+    }
+
+    pub fn transpose(&self) -> SMatrix<T, C, R> {
+        SMatrix::from_fn(|r, c| self.buffer[c][r])
+    }
+
+    pub fn row(&self, r: usize) -> &[T; C] {
+        &self.buffer[r]
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = &[T; C]> {
+        self.buffer.iter()
+    }
+
+    pub fn column(&self, column: usize) -> ColumnRef<'_, T, R, C> {
+        ColumnRef {
+            column,
+            matrix: self,
+        }
+    }
+
+    pub fn norm_squared(&self) -> T {
+        self.buffer
+            .iter()
+            .map(|r| r.iter().map(|x| *x * *x).sum())
+            .sum()
+    }
+}
+
+impl<T: FloatCore + Default + Sum + AddAssign, const R: usize> SVector<T, R> {
+    pub fn zeros() -> Self {
+        SVector {
+            buffer: [T::default(); R],
+        }
+    }
+
+    pub fn from_fn<F: Fn(usize, usize) -> T>(f: F) -> Self {
+        let mut res = Self::zeros();
+        for r in 0..R {
+            res.buffer[r] = f(r, 0);
+        }
+        res
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = &T> {
+        self.buffer.iter()
+    }
+
+    pub fn zip_map<F: Fn(T, T) -> T>(&self, b: &Self, f: F) -> Self {
+        let mut res = Self::zeros();
+        for r in 0..R {
+            res.buffer[r] = f(self.buffer[r], b.buffer[r]);
+        }
+        res
+    }
+
+    pub fn component_mul(&self, b: &Self) -> Self {
+        let mut res = Self::zeros();
+        for r in 0..R {
+            res.buffer[r] = self.buffer[r] * b.buffer[r];
+        }
+        res
+    }
+
+    pub fn norm_squared(&self) -> T {
+        self.buffer.iter().map(|x| *x * *x).sum()
+    }
+
+    pub fn dot(&self, b: &Self) -> T {
+        self.iter().zip(b.iter()).map(|(a, b)| *a * *b).sum()
+    }
+
+    /// Row vector
+    pub fn transpose(&self) -> SMatrix<T, 1, R> {
+        SMatrix::from_fn(|_, c| self.buffer[c])
+    }
+}
+
+impl<T: FloatCore + Default + Sum + AddAssign, const N: usize> SMatrix<T, N, N> {
+    /// LU decomposition with partial pivoting
+    pub fn lu(&self) -> LU<T, N> {
+        let mut lu = self.clone();
+        let mut permutation = [0; N];
+        let odd = factor::lu(lu.buffer.as_flattened_mut(), N, &mut permutation);
+        LU {
+            lu,
+            permutation,
+            odd,
+        }
+    }
+
+    /// None if singular
+    pub fn try_inverse(&self) -> Option<Self> {
+        self.lu().try_inverse()
+    }
+}
+
+#[cfg(any(feature = "std", feature = "libm"))]
+impl<T: FloatCore + Float + Default + Sum + AddAssign, const N: usize> SMatrix<T, N, N> {
+    /// Lower triangular L with L Lᵀ = self, None unless symmetric positive definite
+    pub fn cholesky(&self) -> Option<Cholesky<T, N>> {
+        let mut l = Self::zeros();
+        factor::cholesky(self.buffer.as_flattened(), l.buffer.as_flattened_mut(), N)
+            .then_some(Cholesky { l })
+    }
+}
+
+/// P A = L U, L with unit diagonal below, U on and above the diagonal of `lu`
+pub struct LU<T: FloatCore, const N: usize> {
+    lu: SMatrix<T, N, N>,
+    // row i of P A is row permutation[i] of A
+    permutation: [usize; N],
+    odd: bool,
+}
+
+impl<T: FloatCore + Default + Sum + AddAssign, const N: usize> LU<T, N> {
+    pub fn is_invertible(&self) -> bool {
+        (0..N).all(|i| self.lu.buffer[i][i] != T::zero() && !self.lu.buffer[i][i].is_nan())
+    }
+
+    pub fn determinant(&self) -> T {
+        let det = (0..N).fold(T::one(), |det, i| det * self.lu.buffer[i][i]);
+        if self.odd {
+            -det
+        } else {
+            det
+        }
+    }
+
+    /// Solves A x = b, None if A is singular
+    pub fn solve(&self, b: &SVector<T, N>) -> Option<SVector<T, N>> {
+        if !self.is_invertible() {
+            return None;
+        }
+        let mut x = SVector::zeros();
+        factor::lu_solve(
+            self.lu.buffer.as_flattened(),
+            N,
+            &self.permutation,
+            &b.buffer,
+            &mut x.buffer,
+        );
+        Some(x)
+    }
+
+    pub fn try_inverse(&self) -> Option<SMatrix<T, N, N>> {
+        let mut inverse = SMatrix::zeros();
+        for c in 0..N {
+            let column =
+                self.solve(&SVector::from_fn(
+                    |r, _| if r == c { T::one() } else { T::zero() },
+                ))?;
+            for r in 0..N {
+                inverse.buffer[r][c] = column.buffer[r];
+            }
+        }
+        Some(inverse)
+    }
+}
+
+/// A = L Lᵀ of a symmetric positive definite A, see `SMatrix::cholesky`
+pub struct Cholesky<T: FloatCore, const N: usize> {
+    l: SMatrix<T, N, N>,
+}
+
+impl<T: FloatCore + Default + Sum + AddAssign, const N: usize> Cholesky<T, N> {
+    pub fn l(&self) -> &SMatrix<T, N, N> {
+        &self.l
+    }
+
+    /// Solves A x = b
+    pub fn solve(&self, b: &SVector<T, N>) -> SVector<T, N> {
+        let mut x = b.clone();
+        factor::cholesky_solve(self.l.buffer.as_flattened(), N, &mut x.buffer);
+        x
+    }
+
+    pub fn inverse(&self) -> SMatrix<T, N, N> {
+        let mut inverse = SMatrix::zeros();
+        for c in 0..N {
+            let column = self.solve(&SVector::from_fn(
+                |r, _| if r == c { T::one() } else { T::zero() },
+            ));
+            for r in 0..N {
+                inverse.buffer[r][c] = column.buffer[r];
+            }
+        }
+        inverse
+    }
+}
+
+impl<T: FloatCore + Default + Sum + AddAssign, const R: usize, const C: usize> Mul<SVector<T, C>>
+    for SMatrix<T, R, C>
+{
+    type Output = SVector<T, R>;
+
+    fn mul(self, rhs: SVector<T, C>) -> Self::Output {
+        let mut res = Self::Output::zeros();
+        for r in 0..R {
+            let mut cres = T::default();
+            for c in 0..C {
+                cres += self.buffer[r][c] * rhs.buffer[c];
+            }
+            res.buffer[r] = cres;
+        }
+        res
+    }
+}
+
+// perhaps there is a more elegant way to derive this?
+impl<T: FloatCore + Default + Sum + AddAssign, const R: usize, const C: usize> Mul<&SVector<T, C>>
+    for &SMatrix<T, R, C>
+{
+    type Output = SVector<T, R>;
+
+    fn mul(self, rhs: &SVector<T, C>) -> Self::Output {
+        let mut res = Self::Output::zeros();
+        for r in 0..R {
+            let mut cres = T::default();
+            for c in 0..C {
+                cres += self.buffer[r][c] * rhs.buffer[c];
+            }
+            res.buffer[r] = cres;
+        }
+        res
+    }
+}
+
+impl<T: FloatCore + Default + Sum + AddAssign, const R: usize, const C: usize, const K: usize>
+    Mul<&SMatrix<T, C, K>> for &SMatrix<T, R, C>
+{
+    type Output = SMatrix<T, R, K>;
+
+    fn mul(self, rhs: &SMatrix<T, C, K>) -> Self::Output {
+        let mut res = Self::Output::zeros();
+        for r in 0..R {
+            for c in 0..C {
+                let a = self.buffer[r][c];
+                for k in 0..K {
+                    res.buffer[r][k] += a * rhs.buffer[c][k];
+                }
+            }
+        }
+        res
+    }
+}
+
+impl<T: FloatCore + Default + Sum + AddAssign, const R: usize, const C: usize, const K: usize>
+    Mul<SMatrix<T, C, K>> for SMatrix<T, R, C>
+{
+    type Output = SMatrix<T, R, K>;
+
+    fn mul(self, rhs: SMatrix<T, C, K>) -> Self::Output {
+        &self * &rhs
+    }
+}
+
+// scalar multiplication
+impl<T: FloatCore + Default + Sum + AddAssign, const R: usize> Mul<T> for SVector<T, R> {
+    type Output = SVector<T, R>;
+
+    fn mul(self, rhs: T) -> Self::Output {
+        let mut res = self;
+        for r in 0..R {
+            res.buffer[r] = res.buffer[r] * rhs;
+        }
+        res
+    }
+}
+
+impl<T: FloatCore + Default + Sum + AddAssign, const R: usize, const C: usize> Mul<T>
+    for SMatrix<T, R, C>
+{
+    type Output = SMatrix<T, R, C>;
+
+    fn mul(self, rhs: T) -> Self::Output {
+        let mut res = self;
+        for r in 0..R {
+            for c in 0..C {
+                res.buffer[r][c] = res.buffer[r][c] * rhs;
+            }
+        }
+        res
+    }
+}
+
+impl<T: FloatCore + Default + Sum + AddAssign, const R: usize> Div<T> for SVector<T, R> {
+    type Output = SVector<T, R>;
+
+    fn div(self, rhs: T) -> Self::Output {
+        self * (T::one() / rhs)
+    }
+}
+
+// the scalar on the left only works for concrete types
+macro_rules! scalar_mul {
+    ($($t:ty),*) => {$(
+        impl<const R: usize> Mul<SVector<$t, R>> for $t {
+            type Output = SVector<$t, R>;
+
+            fn mul(self, rhs: SVector<$t, R>) -> Self::Output {
+                rhs * self
+            }
+        }
+
+        impl<const R: usize, const C: usize> Mul<SMatrix<$t, R, C>> for $t {
+            type Output = SMatrix<$t, R, C>;
+
+            fn mul(self, rhs: SMatrix<$t, R, C>) -> Self::Output {
+                rhs * self
+            }
+        }
+    )*};
+}
+
+scalar_mul!(f32, f64);
+
+impl<T: FloatCore + Default + Sum + AddAssign, const R: usize> Add<SVector<T, R>>
+    for SVector<T, R>
+{
+    type Output = SVector<T, R>;
+
+    fn add(self, rhs: SVector<T, R>) -> Self::Output {
+        let mut res = self;
+        for r in 0..R {
+            res.buffer[r] += rhs.buffer[r];
+        }
+        res
+    }
+}
+
+impl<T: FloatCore + Default + Sum + AddAssign, const R: usize> Add<&SVector<T, R>>
+    for &SVector<T, R>
+{
+    type Output = SVector<T, R>;
+
+    fn add(self, rhs: &SVector<T, R>) -> Self::Output {
+        let mut res = self.clone();
+        for r in 0..R {
+            res.buffer[r] += rhs.buffer[r];
+        }
+        res
+    }
+}
+
+impl<T: FloatCore + Default + Sum + AddAssign, const R: usize> Sub<&SVector<T, R>>
+    for &SVector<T, R>
+{
+    type Output = SVector<T, R>;
+
+    fn sub(self, rhs: &SVector<T, R>) -> Self::Output {
+        let mut res = self.clone();
+        for r in 0..R {
+            res.buffer[r] += -rhs.buffer[r];
+        }
+        res
+    }
+}
+
+impl<T: FloatCore + Default + Sum + AddAssign, const R: usize, const C: usize>
+    Add<&SMatrix<T, R, C>> for &SMatrix<T, R, C>
+{
+    type Output = SMatrix<T, R, C>;
+
+    fn add(self, rhs: &SMatrix<T, R, C>) -> Self::Output {
+        let mut res = self.clone();
+        for r in 0..R {
+            for c in 0..C {
+                res.buffer[r][c] += rhs.buffer[r][c];
+            }
+        }
+        res
+    }
+}
+
+impl<T: FloatCore + Default + Sum + AddAssign, const R: usize, const C: usize>
+    Sub<&SMatrix<T, R, C>> for &SMatrix<T, R, C>
+{
+    type Output = SMatrix<T, R, C>;
+
+    fn sub(self, rhs: &SMatrix<T, R, C>) -> Self::Output {
+        let mut res = self.clone();
+        for r in 0..R {
+            for c in 0..C {
+                res.buffer[r][c] += -rhs.buffer[r][c];
+            }
+        }
+        res
+    }
+}
+
+impl<T: FloatCore, const R: usize, const C: usize> Index<(usize, usize)> for SMatrix<T, R, C> {
+    type Output = T;
+
+    fn index(&self, index: (usize, usize)) -> &Self::Output {
+        &self.buffer[index.0][index.1]
+    }
+}
+
+impl<T: FloatCore, const R: usize, const C: usize> IndexMut<(usize, usize)> for SMatrix<T, R, C> {
+    fn index_mut(&mut self, index: (usize, usize)) -> &mut Self::Output {
+        &mut self.buffer[index.0][index.1]
+    }
+}
+
+impl<T: FloatCore, const R: usize> Index<(usize, usize)> for SVector<T, R> {
+    type Output = T;
+
+    fn index(&self, index: (usize, usize)) -> &Self::Output {
+        &self.buffer[index.0]
+    }
+}
+
+impl<T: FloatCore, const R: usize> IndexMut<(usize, usize)> for SVector<T, R> {
+    fn index_mut(&mut self, index: (usize, usize)) -> &mut Self::Output {
+        &mut self.buffer[index.0]
+    }
+}
+
+impl<T: FloatCore, const R: usize> Neg for SVector<T, R> {
+    type Output = Self;
+
+    fn neg(self) -> Self::Output {
+        let mut res = self;
+        for r in 0..R {
+            res.buffer[r] = -res.buffer[r];
+        }
+        res
+    }
+}
+
+impl<T: FloatCore, const R: usize, const C: usize> Neg for SMatrix<T, R, C> {
+    type Output = Self;
+
+    fn neg(self) -> Self::Output {
+        let mut res = self;
+        for r in 0..R {
+            for c in 0..C {
+                res.buffer[r][c] = -res.buffer[r][c];
+            }
+        }
+        res
+    }
+}
+
+impl<T: FloatCore + Default + Sum + AddAssign + Debug, const R: usize, const C: usize> Debug
+    for SMatrix<T, R, C>
+{
+    fn fmt(&self, fmt: &mut Formatter<'_>) -> Result<(), core::fmt::Error> {
+        fmt.write_str("[\n")?;
+        for r in self.iter() {
+            for c in r.iter() {
+                c.fmt(fmt)?;
+                fmt.write_char(' ')?;
+            }
+            fmt.write_char('\n')?;
+        }
+        fmt.write_char(']')?;
+        Ok(())
+    }
+}
+
+impl<T: FloatCore + Default + Sum + AddAssign + Debug, const R: usize> Debug for SVector<T, R> {
+    fn fmt(&self, fmt: &mut Formatter<'_>) -> Result<(), core::fmt::Error> {
+        fmt.write_char('[')?;
+        for r in self.iter() {
+            r.fmt(fmt)?;
+            fmt.write_char(' ')?;
+        }
+        fmt.write_char(']')?;
+        Ok(())
+    }
+}
+
+// row by row
+#[cfg(feature = "std")]
+impl<T: FloatCore, const R: usize, const C: usize> From<SMatrix<T, R, C>>
+    for std::vec::Vec<std::vec::Vec<T>>
+{
+    fn from(m: SMatrix<T, R, C>) -> Self {
+        m.buffer.iter().map(|row| row.to_vec()).collect()
+    }
+}
+
+#[cfg(feature = "std")]
+impl<T: FloatCore, const R: usize> From<SVector<T, R>> for std::vec::Vec<T> {
+    fn from(v: SVector<T, R>) -> Self {
+        v.buffer.to_vec()
+    }
+}
+
+impl<'a, T: FloatCore + Default + Sum + AddAssign, const R: usize, const C: usize>
+    ColumnRef<'a, T, R, C>
+{
+    pub fn iter(&self) -> ColumnRefIter<'a, T, R, C> {
+        ColumnRefIter {
+            column: self.column,
+            row: 0,
+            matrix: self.matrix,
+        }
+    }
+}
+
+impl<'a, T: FloatCore + Default + Sum + AddAssign, const R: usize, const C: usize> Iterator
+    for ColumnRefIter<'a, T, R, C>
+{
+    type Item = &'a T;
+
+    fn next(&mut self) -> Option<&'a T> {
+        self.row += 1;
+        if self.row > R {
+            return None;
+        }
+        Some(&self.matrix.buffer[self.row - 1][self.column])
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{SMatrix, SVector};
+    use core::{cmp::PartialEq, fmt::Debug, iter::Sum, ops::AddAssign};
+    use num_traits::float::FloatCore;
+    use std::{vec, vec::Vec};
+
+    impl<T: FloatCore + Default + Sum + AddAssign + Debug, const R: usize, const C: usize>
+        PartialEq<[[T; C]; R]> for SMatrix<T, R, C>
+    {
+        fn eq(&self, other: &[[T; C]; R]) -> bool {
+            for r in 0..R {
+                for c in 0..C {
+                    if self[(r, c)] != other[r][c] {
+                        return false;
+                    }
+                }
+            }
+            true
+        }
+    }
+    impl<T: FloatCore + Default + Sum + AddAssign + Debug, const R: usize> PartialEq<[T; R]>
+        for SVector<T, R>
+    {
+        fn eq(&self, other: &[T; R]) -> bool {
+            for r in 0..R {
+                if self[(r, 0)] != other[r] {
+                    return false;
+                }
+            }
+            true
+        }
+    }
+
+    #[test]
+    pub fn test_mini_nalgebra() {
+        assert_eq!(SMatrix::<f32, 2, 2>::zeros(), [[0.0, 0.0], [0.0, 0.0]]);
+        assert_eq!(SVector::<f32, 2>::zeros(), [0.0, 0.0]);
+        assert_eq!(
+            SMatrix::<f32, 2, 2>::from_fn(|r, c| (r as f32) * 2.0 + (c as f32)),
+            [[0.0, 1.0], [2.0, 3.0]]
+        );
+        let a = SMatrix::<f32, 2, 2>::from_fn(|r, c| (r as f32) * 2.0 + (c as f32));
+        assert_eq!(a.row(0), &[0.0_f32, 1.0]);
+        assert_eq!(
+            a.iter().next().and_then(|r| r.iter().next()).copied(),
+            Some(0.0)
+        );
+        assert_eq!(a.iter().nth(1).and_then(|r| r.get(1)).copied(), Some(3.0));
+        assert_eq!(
+            a.column(1).iter().copied().collect::<Vec<f32>>(),
+            vec![1.0, 3.0]
+        );
+        assert_eq!(a.norm_squared(), 14.0);
+        let b = SVector::<f32, 2>::from_fn(|r, _| (r + 1) as f32);
+        assert_eq!(b, [1.0, 2.0]);
+        let c = SVector::<f32, 2>::from_fn(|r, _| 2.5 - (r as f32));
+        assert_eq!(c, [2.5, 1.5]);
+        assert_eq!(b.zip_map(&c, |a, b| a * b), [2.5, 3.0]);
+        assert_eq!(b.component_mul(&c), [2.5, 3.0]);
+        assert_eq!(c.norm_squared(), 8.5);
+        assert_eq!(a.clone() * b.clone(), [2.0, 8.0]);
+        assert_eq!(&a * &b, [2.0, 8.0]);
+        assert_eq!(3.0 * b.clone(), [3.0, 6.0]);
+        assert_eq!(b.clone() + c.clone(), [3.5, 3.5]);
+        assert_eq!(&b + &c, [3.5, 3.5]);
+        let d = SMatrix::<f32, 2, 2>::from_fn(|r, c| ((2 - r) as f32) * 2.0 + ((2 - c) as f32));
+        assert_eq!(d, [[6.0, 5.0], [4.0, 3.0]]);
+        assert_eq!(&d - &a, [[6.0, 4.0], [2.0, 0.0]]);
+        assert_eq!(d[(1, 1)], 3.0);
+        assert_eq!(c[(1, 0)], 1.5);
+        assert_eq!(-d.clone(), [[-6.0, -5.0], [-4.0, -3.0]]);
+    }
+
+    #[test]
+    #[cfg(feature = "std")]
+    pub fn test_vec() {
+        let a = SMatrix::<f32, 2, 2>::from_fn(|r, c| (r as f32) * 2.0 + (c as f32));
+        assert_eq!(
+            Vec::<Vec<f32>>::from(a),
+            vec![vec![0.0, 1.0], vec![2.0, 3.0]]
+        );
+        assert_eq!(
+            Vec::from(SVector::<f32, 2>::from_fn(|r, _| r as f32)),
+            vec![0.0, 1.0]
+        );
+    }
+
+    #[test]
+    pub fn test_linear_algebra() {
+        let a = SMatrix::<f64, 2, 3>::from_fn(|r, c| (r * 3 + c) as f64);
+        assert_eq!(a.transpose(), [[0.0, 3.0], [1.0, 4.0], [2.0, 5.0]]);
+        assert_eq!(&a * &a.transpose(), [[5.0, 14.0], [14.0, 50.0]]);
+        assert_eq!(a.clone() * SMatrix::<f64, 3, 3>::identity(), a);
+        assert_eq!(
+            SMatrix::<f64, 2, 3>::identity(),
+            [[1.0, 0.0, 0.0], [0.0, 1.0, 0.0]]
+        );
+        assert_eq!(a.clone() * 2.0, 2.0 * a.clone());
+        assert_eq!(
+            SVector::<f64, 2>::from_fn(|r, _| r as f64) * 3.0,
+            [0.0, 3.0]
+        );
+        assert_eq!(
+            SVector::<f64, 2>::from_fn(|r, _| r as f64).transpose(),
+            [[0.0, 1.0]]
+        );
+
+        // needs pivoting: the first pivot is zero
+        let b = SMatrix::<f64, 3, 3>::from_fn(|r, c| {
+            [[0.0, 2.0, 1.0], [1.0, 1.0, 0.0], [2.0, 1.0, 3.0]][r][c]
+        });
+        let lu = b.lu();
+        assert!((lu.determinant() - -7.0).abs() < 1e-12);
+        let x = lu
+            .solve(&SVector::from_fn(|r, _| [3.0, 2.0, 6.0][r]))
+            .unwrap();
+        assert!((&x - &SVector::from_fn(|_, _| 1.0)).norm_squared() < 1e-24);
+        let inverse = b.try_inverse().unwrap();
+        assert!((&(&b * &inverse) - &SMatrix::identity()).norm_squared() < 1e-24);
+        let singular = SMatrix::<f64, 2, 2>::from_fn(|r, c| ((r + 1) * (c + 1)) as f64);
+        assert!(singular.try_inverse().is_none());
+        assert_eq!(singular.lu().determinant(), 0.0);
+        let mut broken = b.clone();
+        broken.buffer[1][0] = f64::NAN;
+        assert!(broken.try_inverse().is_none());
+        assert!(broken.lu().solve(&SVector::from_fn(|_, _| 1.0)).is_none());
+    }
+
+    #[test]
+    #[cfg(any(feature = "std", feature = "libm"))]
+    pub fn test_cholesky() {
+        let b = SMatrix::<f64, 3, 3>::from_fn(|r, c| {
+            [[0.0, 2.0, 1.0], [1.0, 1.0, 0.0], [2.0, 1.0, 3.0]][r][c]
+        });
+        let spd = &b.transpose() * &b;
+        let cholesky = spd.cholesky().unwrap();
+        let l = cholesky.l();
+        assert!((&(l * &l.transpose()) - &spd).norm_squared() < 1e-20);
+        assert!((&(&spd * &cholesky.inverse()) - &SMatrix::identity()).norm_squared() < 1e-20);
+        let singular = SMatrix::<f64, 2, 2>::from_fn(|r, c| ((r + 1) * (c + 1)) as f64);
+        assert!(singular.cholesky().is_none());
+    }
+
+    // one damped Gauss-Newton step on the recipe matrix of the economy, as in gauss_newton.rs
+    #[test]
+    #[cfg(any(feature = "std", feature = "libm"))]
+    pub fn test_gauss_newton_step() {
+        let x = SMatrix::<f32, 4, 5>::from_fn(|r, c| {
+            [
+                [10.0, -10.0, 0.0, 0.0, 0.0],
+                [0.0, 10.0, -0.1, 0.0, -0.2],
+                [0.0, 0.0, 1.0, 1.0, -1.0],
+                [0.0, 0.0, 0.0, 0.0, 1.0],
+            ][r][c]
+        });
+        let y = SVector::<f32, 4>::from_fn(|r, _| if r == 3 { 50.0 } else { 0.01 });
+        let beta = SVector::<f32, 5>::from_fn(|_, _| 1.0);
+        let residual = &y - &(&x * &beta);
+        let d = &(&x.transpose() * &x) + &(SMatrix::<f32, 5, 5>::identity() * 1e-3);
+        let step = d.cholesky().unwrap().solve(&(&x.transpose() * &residual));
+        let beta = &beta + &step;
+        assert!((&y - &(&x * &beta)).norm_squared() < 1e-3 * residual.norm_squared());
+    }
+}
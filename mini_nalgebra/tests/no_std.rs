@@ -0,0 +1,37 @@
+// The library has to build without std, with and without libm and alloc
+use std::process::Command;
+
+fn build(features: &[&str]) {
+    let mut command = Command::new(option_env!("CARGO").unwrap_or("cargo"));
+    command
+        .args(["build", "--lib", "--no-default-features", "--manifest-path"])
+        .arg(concat!(env!("CARGO_MANIFEST_DIR"), "/Cargo.toml"))
+        // a target directory of its own, the one of the test run is locked
+        .arg("--target-dir")
+        .arg(concat!(env!("CARGO_TARGET_TMPDIR"), "/no_std"));
+    if !features.is_empty() {
+        command.arg("--features").arg(features.join(","));
+    }
+    let output = command.output().expect("can't run cargo");
+    assert!(
+        output.status.success(),
+        "no_std build with {:?} failed:\n{}",
+        features,
+        String::from_utf8_lossy(&output.stderr)
+    );
+}
+
+#[test]
+fn core_only() {
+    build(&[]);
+}
+
+#[test]
+fn with_libm() {
+    build(&["libm"]);
+}
+
+#[test]
+fn with_alloc() {
+    build(&["alloc", "libm"]);
+}
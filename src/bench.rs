@@ -0,0 +1,90 @@
+// Small benchmark harness on std::time::Instant, for benches/ and the experiments in src/bin
+
+use std::fmt;
+use std::hint::black_box;
+use std::time::{Duration, Instant};
+
+/// Summary of the repetitions of one benchmark, times in seconds
+#[derive(Clone, Debug)]
+pub struct Stats {
+    pub name: String,
+    pub repetitions: u32,
+    pub min: f64,
+    pub median: f64,
+    pub mean: f64,
+    pub std_dev: f64,
+}
+
+impl fmt::Display for Stats {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "{:<40} median {:>10}  mean {:>10} ± {:>5.1}%  min {:>10}  ({} runs)",
+            self.name,
+            seconds(self.median),
+            seconds(self.mean),
+            100.0 * self.std_dev / self.mean,
+            seconds(self.min),
+            self.repetitions
+        )
+    }
+}
+
+// time with a unit that keeps 3-4 digits
+fn seconds(s: f64) -> String {
+    if s >= 1.0 {
+        format!("{:.3} s", s)
+    } else if s >= 1e-3 {
+        format!("{:.3} ms", s * 1e3)
+    } else if s >= 1e-6 {
+        format!("{:.3} µs", s * 1e6)
+    } else {
+        format!("{:.1} ns", s * 1e9)
+    }
+}
+
+/// Runs `f` for `warm_up` first, then `repetitions` times. Every repetition calls `f` often
+/// enough to take about a millisecond so short functions are not lost in the timer
+/// resolution, and reports the time per call.
+pub fn bench<R>(
+    name: &str,
+    warm_up: Duration,
+    repetitions: u32,
+    mut f: impl FnMut() -> R,
+) -> Stats {
+    let start = Instant::now();
+    let mut calls = 0u32;
+    while calls == 0 || start.elapsed() < warm_up {
+        black_box(f());
+        calls += 1;
+    }
+    let per_call = start.elapsed().as_secs_f64() / calls as f64;
+    let batch = ((1e-3 / per_call.max(1e-9)) as u32).max(1);
+
+    let mut times: Vec<f64> = (0..repetitions.max(1))
+        .map(|_| {
+            let start = Instant::now();
+            for _ in 0..batch {
+                black_box(f());
+            }
+            start.elapsed().as_secs_f64() / batch as f64
+        })
+        .collect();
+    times.sort_by(|a, b| a.partial_cmp(b).unwrap());
+    let n = times.len() as f64;
+    let mean = times.iter().sum::<f64>() / n;
+    let variance = times.iter().map(|t| (t - mean) * (t - mean)).sum::<f64>() / n;
+    let median = if times.len() % 2 == 1 {
+        times[times.len() / 2]
+    } else {
+        (times[times.len() / 2 - 1] + times[times.len() / 2]) / 2.0
+    };
+    Stats {
+        name: name.to_string(),
+        repetitions: times.len() as u32,
+        min: times[0],
+        median,
+        mean,
+        std_dev: variance.sqrt(),
+    }
+}
@@ -1,5 +1,11 @@
-mod mini_nalgebra;
-use mini_nalgebra::{SMatrix, SVector};
+use economy3::bench::bench;
+use economy3::matrix_io;
+use economy3::recipe_graph::{Propagation, RecipeGraph};
+use economy3::rs_leastsquare::{pseudo_inverse, Truncation};
+use economy3::sparse::{lsqr, CsrMatrix};
+use economy3::supply_chain::Generator;
+use mini_nalgebra::{DMatrix, DVector, SMatrix, SVector};
+use std::time::Duration;
 
 /// for a given row (ware) calculate the labor effort to produce it
 /// (recursively)
@@ -71,323 +77,182 @@ const GLENGTH: usize = 51;
 const LLENGTH: usize = 44;
 
 /// calculate the laborers to add per unit of ware
-fn my_neg_inverse(
+/// (path by path up to `depth`, kept as the regression oracle for `my_neg_inverse`)
+fn my_neg_inverse_recursive(
     x: &SMatrix<f32, { GLENGTH }, { LLENGTH }>,
     productivity: &SVector<f32, { LLENGTH }>,
+    depth: u32,
 ) -> SMatrix<f32, { LLENGTH }, { GLENGTH }> {
     let mut res = SMatrix::zeros();
     for row in 0..GLENGTH {
-        inv_recurse(&mut res, x, productivity, row, row, -1.0, 5);
+        inv_recurse(&mut res, x, productivity, row, row, -1.0, depth);
     }
     res
 }
 
-const TESTX: [[f32; LLENGTH]; GLENGTH] = [
-    [
-        16.019226, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0,
-        0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0,
-        0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0,
-    ],
-    [
-        0.0, 16.019226, 16.36428, 16.365808, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0,
-        0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0,
-        0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0,
-    ],
-    [
-        0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0,
-        0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0,
-        0.0, 0.0, 0.0, 0.0, -0.2503004, -1.0012016, -4.0167003, -3.0125248,
-    ],
-    [
-        0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0,
-        0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0,
-        0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 4.0167003, 0.0,
-    ],
-    [
-        0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0,
-        0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0,
-        0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 3.0125248,
-    ],
-    [
-        0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0,
-        0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0,
-        0.0, 0.0, 0.0, 0.0, 0.0, 2.0024033, 0.0, 0.0,
-    ],
-    [
-        0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0,
-        0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0,
-        0.0, 0.0, 0.0, 0.0, 1.0012016, 0.0, 0.0, 0.0,
-    ],
-    [
-        0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0,
-        0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0,
-        0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0,
-    ],
-    [
-        0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0,
-        0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 50.06008, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0,
-        -0.1, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0,
-    ],
-    [
-        0.0, 0.0, 0.0, 0.0, -0.5, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0,
-        0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 50.06008, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0,
-        0.0, -0.1, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0,
-    ],
-    [
-        0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0, -4.0,
-        0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 50.06008, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0,
-        0.0, 0.0, -0.1, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0,
-    ],
-    [
-        0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0, -4.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0,
-        0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 50.06008, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0,
-        0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0,
-    ],
-    [
-        0.0, 0.0, 0.0, 0.0, 0.0, -0.5, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0,
-        0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 50.06008, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0,
-        0.0, 0.0, 0.0, -0.1, 0.0, 0.0, 0.0, 0.0, 0.0,
-    ],
-    [
-        0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0, -4.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0,
-        0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 50.06008, 0.0, 0.0, 0.0, 0.0, 0.0,
-        0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0,
-    ],
-    [
-        -4.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0,
-        0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 50.06008, 0.0, 0.0, 0.0, 0.0,
-        0.0, 0.0, 0.0, 0.0, -0.5, 0.0, 0.0, 0.0, 0.0,
-    ],
-    [
-        0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0,
-        0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0,
-        0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0,
-    ],
-    [
-        0.0, -13.200001, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0,
-        16.019226, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0,
-        0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0,
-    ],
-    [
-        0.0, -5.0, -20.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 4.0048065, 4.0048065, 4.41532, 0.0,
-        0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0,
-        0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0,
-    ],
-    [
-        0.0, 0.0, 0.0, 0.0, 0.5006008, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0,
-        0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0,
-        0.0, 0.0, 0.0, 0.0, 0.0, -1.1013218, -2.2026436, 0.0, 0.0,
-    ],
-    [
-        0.0, 0.0, 0.0, 0.0, 0.0, 0.5006008, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0,
-        0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0,
-        0.0, 0.0, 0.0, 0.0, 0.0, -4.0048065, 0.0, 0.0, 0.0,
-    ],
-    [
-        0.0,
-        0.0,
-        0.0,
-        0.0,
-        0.0,
-        0.0,
-        0.0,
-        0.0,
-        0.0,
-        0.0,
-        0.0,
-        0.0,
-        0.0,
-        0.0,
-        0.0,
-        0.0,
-        0.0,
-        0.0,
-        0.0,
-        2.0024033,
-        0.100120164,
-        0.0,
-        0.0,
-        0.0,
-        0.0,
-        0.0,
-        0.0,
-        0.0,
-        0.0,
-        0.0,
-        0.0,
-        0.0,
-        0.0,
-        0.0,
-        0.0,
-        0.0,
-        0.0,
-        0.0,
-        0.0,
-        0.0,
-        0.0,
-        0.0,
-        0.0,
-        0.0,
-    ],
-    [
-        0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0,
-        0.0, 0.0, 0.0, 0.0, 3.003605, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0,
-        0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0,
-    ],
-    [
-        0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0,
-        0.0, 0.0, 0.0, 0.0, 0.0, 5.006008, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0,
-        0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0,
-    ],
-    [
-        0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0,
-        0.0, 0.0, 0.0, 4.0048065, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0,
-        0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0,
-    ],
-    [
-        0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0,
-        0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0,
-        0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0,
-    ],
-    [
-        0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0, -1.2, -1.2, -1.2, -1.2, -1.2,
-        0.0, 6.873252, 0.0, 0.0, 0.0, 0.0, 0.0, -4.8, -4.8, -4.8, -4.8, -4.8, -4.8, -3.6000001,
-        -3.6000001, -3.6000001, -3.6000001, -3.6000001, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0,
-        0.0,
-    ],
-    [
-        0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0,
-        0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0,
-        0.0, 0.0, 0.0, 30.036049, 0.0, 0.0, 0.0, 0.0,
-    ],
-    [
-        0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0, -4.0, 0.0, 0.0, 0.0, 0.0,
-        0.0, 0.0, 0.0, 0.0, 0.0, 0.0, -50.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0, -10.0, 0.0, 0.0, 0.0,
-        0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0,
-    ],
-    [
-        0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0, -4.0, 0.0, 0.0, 0.0,
-        0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0, -50.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0, -10.0, 0.0, 0.0,
-        0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0,
-    ],
-    [
-        0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0, -4.0, 0.0, 0.0,
-        0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0, -50.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0, -10.0, 0.0,
-        0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0,
-    ],
-    [
-        0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0,
-        0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0, -50.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0,
-        0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0,
-    ],
-    [
-        0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0, -4.0, 0.0, 0.0, 0.0, -4.0, 0.0,
-        0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0, -50.0, 0.0, 0.0, 0.0, 0.0, 0.0, -10.0,
-        0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0,
-    ],
-    [
-        0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0,
-        0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0, -50.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0,
-        0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0,
-    ],
-    [
-        0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0,
-        0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0,
-        0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0,
-    ],
-    [
-        0.0, 0.0, 0.0, 0.0, 0.0, 0.0, -0.5, -0.5, -0.5, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0,
-        0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0,
-        0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0,
-    ],
-    [
-        -12.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.52674425, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0,
-        0.0, 0.0, 0.0, -4.0, 0.0, -8.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0,
-        0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0,
-    ],
-    [
-        0.0, -1.545, -2.5749998, -2.5749998, 0.0, 0.0, 0.65765226, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0,
-        0.0, 0.0, 0.0, 0.0, 0.0, -0.515, -1.03, 0.0, -2.06, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0,
-        0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0,
-    ],
-    [
-        0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0,
-        0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0,
-        0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0,
-    ],
-    [
-        0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.5006008, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0,
-        0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0,
-        0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0,
-    ],
-    [
-        0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0, -0.5, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0,
-        0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0,
-        0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0,
-    ],
-    [
-        0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.5006008, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0,
-        0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0,
-        0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0,
-    ],
-    [
-        0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0,
-        0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 10.012016, 0.0, 0.0, 0.0,
-        4.0048065, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0,
-    ],
-    [
-        0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0,
-        0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 10.012016, 0.0, 0.0,
-        0.0, 4.0048065, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0,
-    ],
-    [
-        0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0,
-        0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 10.012016, 0.0,
-        0.0, 0.0, 4.0048065, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0,
-    ],
-    [
-        0.0, 0.0, 0.0, -38.4, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0,
-        -4.8, 0.0, 0.0, 0.0, -9.6, -0.6, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0,
-        11.717368, 0.0, 0.0, 0.0, 4.692347, 0.0, 0.0, 0.0, -28.920242, 0.0,
-    ],
-    [
-        0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0,
-        0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0,
-        0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0,
-    ],
-    [
-        0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 4.0048065, 0.0, 0.0, 0.0,
-        0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0,
-        0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0,
-    ],
-    [
-        0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 4.0048065, 0.0, 0.0,
-        0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0,
-        0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0,
-    ],
-    [
-        0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 4.0048065, 0.0,
-        0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0,
-        0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0,
-    ],
-    [
-        0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 4.8064933,
-        0.0, 0.0, 0.0, -1.2, 0.0, -1.2, -7.2000003, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0,
-        0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0, -21.69018,
-    ],
-    [
-        0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0,
-        0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0,
-        0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0,
-    ],
-];
+/// calculate the laborers to add per unit of ware
+/// Same as `my_neg_inverse_recursive` without the depth limit: each ware's effort is
+/// computed once from the efforts of its inputs, in topological order of the recipe graph.
+/// Cycles are solved exactly by `RecipeGraph::propagate`, non-productive ones are reported.
+fn my_neg_inverse(
+    x: &SMatrix<f32, { GLENGTH }, { LLENGTH }>,
+    productivity: &SVector<f32, { LLENGTH }>,
+) -> SMatrix<f32, { LLENGTH }, { GLENGTH }> {
+    neg_effort(&recipe_graph(x, productivity).propagate(LLENGTH))
+}
+
+/// `my_neg_inverse` with the independent parts of the supply chain on all threads,
+/// bit-identical to the serial version
+fn my_neg_inverse_parallel(
+    x: &SMatrix<f32, { GLENGTH }, { LLENGTH }>,
+    productivity: &SVector<f32, { LLENGTH }>,
+) -> SMatrix<f32, { LLENGTH }, { GLENGTH }> {
+    neg_effort(&recipe_graph(x, productivity).propagate_parallel(LLENGTH))
+}
+
+fn recipe_graph(
+    x: &SMatrix<f32, { GLENGTH }, { LLENGTH }>,
+    productivity: &SVector<f32, { LLENGTH }>,
+) -> RecipeGraph<f32> {
+    RecipeGraph::new(
+        GLENGTH,
+        LLENGTH,
+        |r, c| x[(r, c)],
+        |c| productivity[(c, 0)],
+        0.001,
+    )
+}
+
+fn neg_effort(propagation: &Propagation<f32>) -> SMatrix<f32, { LLENGTH }, { GLENGTH }> {
+    report_cycles(propagation);
+    SMatrix::from_fn(|r, c| -propagation.effort[c][r])
+}
 
+fn report_cycles(propagation: &Propagation<f32>) {
+    for cycle in &propagation.non_productive {
+        println!("non-productive cycle {:?} with gain {}", cycle.goods, cycle.gain);
+    }
+}
+
+/// `my_neg_inverse` for any number of wares and labors, the result lives on the heap
+fn my_neg_inverse_dynamic(x: &DMatrix<f32>, productivity: &DVector<f32>) -> DMatrix<f32> {
+    let (wares, labors) = x.shape();
+    let graph =
+        RecipeGraph::new(wares, labors, |r, c| x[(r, c)], |c| productivity[(c, 0)], 0.001);
+    let propagation = graph.propagate(labors);
+    report_cycles(&propagation);
+    DMatrix::from_fn(labors, wares, |r, c| -propagation.effort[c][r])
+}
+
+const DEFAULT_MATRIX: &str = concat!(env!("CARGO_MANIFEST_DIR"), "/fixtures/testx.mtx");
+
+/// usage: pseudo_inverse_speed [matrix file]
+///
+/// The matrix has a row per ware and a column per labor (.mtx, .npy or .csv, see matrix_io),
+/// by default fixtures/testx.mtx. The fixed size versions only run on 51 x 44 matrices.
 fn main() {
-    let x = SMatrix::<f32, GLENGTH, LLENGTH>::from_fn(|r,c| TESTX[r][c]);
-    let prod= SVector::<f32, LLENGTH>::from_fn(|_,_|1.0);
-    let a=amd64_timer::ticks();
-    let y = my_neg_inverse(&x, &prod);
-    let b=amd64_timer::ticks();
-    dbg!(b-a);
-    dbg!(y.norm_squared());
+    let path = std::env::args().nth(1).unwrap_or_else(|| DEFAULT_MATRIX.to_string());
+    let xd = matrix_io::read(&path).expect("can't read matrix file").map(|v| v as f32);
+    let (wares, labors) = xd.shape();
+    println!("{}: {} wares x {} labors", path, wares, labors);
+    let warm_up = Duration::from_millis(200);
+
+    let timing = bench("pseudo_inverse", warm_up, 20, || {
+        pseudo_inverse(&xd, Truncation::Relative(1e-6))
+    });
+    println!("{}", timing);
+    let pinv = pseudo_inverse(&xd, Truncation::Relative(1e-6));
+
+    if (wares, labors) == (GLENGTH, LLENGTH) {
+        let x = SMatrix::<f32, GLENGTH, LLENGTH>::from_fn(|r, c| xd[(r, c)]);
+        let prod= SVector::<f32, LLENGTH>::from_fn(|_,_|1.0);
+        let recursive = bench("my_neg_inverse_recursive", warm_up, 20, || {
+            my_neg_inverse_recursive(&x, &prod, 5)
+        });
+        println!("{}", recursive);
+        let recursive = my_neg_inverse_recursive(&x, &prod, 5);
+        dbg!(recursive.norm_squared());
+        let serial = bench("my_neg_inverse", warm_up, 20, || my_neg_inverse(&x, &prod));
+        let parallel = bench("my_neg_inverse_parallel", warm_up, 20, || {
+            my_neg_inverse_parallel(&x, &prod)
+        });
+        println!("{}\n{}", serial, parallel);
+        let y = my_neg_inverse(&x, &prod);
+        dbg!(y.norm_squared());
+        assert!(y.iter().eq(my_neg_inverse_parallel(&x, &prod).iter()));
+        let dynamic = my_neg_inverse_dynamic(&DMatrix::from(&x), &DVector::from(&prod));
+        assert!(dynamic == DMatrix::from(&y));
+        println!("parallel speedup: {:.2}", serial.median / parallel.median);
+        // what depth 5 cuts off (see the oracle test)
+        dbg!((&y - &recursive).norm_squared());
+
+        // compare with the exact (negated) pseudo inverse
+        let diff = SMatrix::<f32, LLENGTH, GLENGTH>::from_fn(|r, c| y[(r, c)] + pinv[(r, c)]);
+        dbg!(diff.norm_squared());
+    } else {
+        let graph = RecipeGraph::new(wares, labors, |r, c| xd[(r, c)], |_| 1.0_f32, 0.001);
+        let serial = bench("propagate", warm_up, 20, || graph.propagate(labors));
+        let parallel = bench("propagate_parallel", warm_up, 20, || {
+            graph.propagate_parallel(labors)
+        });
+        println!("{}\n{}", serial, parallel);
+        let effort = graph.propagate(labors).effort;
+        let diff = nalgebra::DMatrix::from_fn(labors, wares, |r, c| effort[c][r] - pinv[(r, c)]);
+        dbg!(diff.norm_squared());
+    }
+
+    // sparse least squares: laborers needed for one unit of every ware
+    let sparse = CsrMatrix::from_dense(&xd);
+    let ones = nalgebra::DVector::from_element(wares, 1.0_f32);
+    println!("{}", bench("lsqr", warm_up, 20, || lsqr(&sparse, &ones, 1e-6, 1000)));
+    let solution = lsqr(&sparse, &ones, 1e-6, 1000);
+    dbg!(sparse.nnz(), solution.iterations, solution.residual_norm);
+
+    // parallel speedup on larger supply chains
+    println!("{} threads", rayon::current_num_threads());
+    for goods in [200, 1000, 3000] {
+        let chain = Generator {
+            goods,
+            ..Generator::default()
+        }
+        .generate(42);
+        let x = chain.recipe_matrix();
+        let labors = x.ncols();
+        let graph = RecipeGraph::new(goods, labors, |r, c| x[(r, c)], |_| 1.0_f32, 0.001);
+        let serial = bench(&format!("propagate {}", goods), warm_up, 10, || {
+            graph.propagate(labors)
+        });
+        let parallel = bench(&format!("propagate_parallel {}", goods), warm_up, 10, || {
+            graph.propagate_parallel(labors)
+        });
+        assert!(graph.propagate(labors).effort == graph.propagate_parallel(labors).effort);
+        // a 3000 x 3000 SMatrix would not fit on the stack
+        let xm = DMatrix::from_fn(goods, labors, |r, c| x[(r, c)]);
+        let y = my_neg_inverse_dynamic(&xm, &DVector::from_fn(labors, |_, _| 1.0));
+        dbg!(y.norm_squared());
+        println!("{}\n{}\nspeedup {:.2}", serial, parallel, serial.median / parallel.median);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn recursive_oracle() {
+        let xd = matrix_io::read(DEFAULT_MATRIX).unwrap();
+        let x = SMatrix::<f32, GLENGTH, LLENGTH>::from_fn(|r, c| xd[(r, c)] as f32);
+        let prod = SVector::<f32, LLENGTH>::from_fn(|_, _| 1.0);
+        let y = my_neg_inverse(&x, &prod);
+        // testx contains a cycle, which the recursion cuts off at its depth; going deeper it
+        // converges to the exact propagation
+        let error = |depth| {
+            let oracle = my_neg_inverse_recursive(&x, &prod, depth);
+            (&y - &oracle).norm_squared() / oracle.norm_squared()
+        };
+        let shallow = error(5);
+        let deep = error(10);
+        assert!(deep < 1e-2 * shallow);
+        assert!(deep <= 1e-8);
+    }
 }
@@ -0,0 +1,76 @@
+/// Singular value analysis of a recipe matrix (goods × labors), replaces econ.py
+///
+/// usage: svd_report [matrix file]
+///
+/// Without a file the recipe matrix of the economy is analyzed. The file is MatrixMarket
+/// (.mtx), numpy (.npy) or text with one row per line, entries separated by commas or
+/// whitespace; an optional first line starting with `#` names the columns (see matrix_io).
+use economy3::economy::{Economy, LABORS};
+use economy3::matrix_io;
+use economy3::rs_leastsquare::null_space;
+use nalgebra::DMatrix;
+
+// singular values below this fraction of the largest one count as zero (f32 data)
+const TOLERANCE: f64 = 1e-6;
+
+fn main() {
+    let (x, labels) = match std::env::args().nth(1) {
+        Some(path) => matrix_io::read_labeled(&path).expect("can't read matrix file"),
+        None => {
+            let x = Economy::<f64>::new(100.0).recipe_matrix();
+            let labels = LABORS.iter().map(|l| format!("{:?}", l)).collect();
+            (
+                DMatrix::from_fn(x.nrows(), x.ncols(), |r, c| x[(r, c)]),
+                Some(labels),
+            )
+        }
+    };
+    let labels = labels.unwrap_or_else(|| (0..x.ncols()).map(|c| format!("#{}", c)).collect());
+    assert_eq!(labels.len(), x.ncols(), "one label per column needed");
+
+    println!("matrix: {} goods x {} labors", x.nrows(), x.ncols());
+    if x.is_empty() {
+        println!("nothing to analyze");
+        return;
+    }
+    let sigma = x.clone().svd(false, false).singular_values;
+    let eps = sigma.max() * TOLERANCE;
+    let rank = sigma.iter().filter(|&&s| s > eps).count();
+    print!("singular values:");
+    for s in sigma.iter() {
+        print!(" {:.4}", s);
+    }
+    println!();
+    println!("numerical rank: {} (tolerance {:e})", rank, eps);
+    if rank == 0 {
+        println!("condition number: infinite (zero matrix)");
+    } else if rank == x.ncols() {
+        println!("condition number: {:.4}", sigma.max() / sigma[rank - 1]);
+    } else {
+        println!(
+            "condition number: infinite ({:.4} on the rank {} part)",
+            sigma.max() / sigma[rank - 1],
+            rank
+        );
+    }
+
+    let basis = null_space(&x, TOLERANCE);
+    if basis.ncols() == 0 {
+        println!("all labors are determined by the goods balance");
+    }
+    for (i, v) in basis.column_iter().enumerate() {
+        // only show participating labors, largest first
+        let mut entries: Vec<(usize, f64)> = v
+            .iter()
+            .copied()
+            .enumerate()
+            .filter(|(_, a)| a.abs() > 1e-3)
+            .collect();
+        entries.sort_by(|a, b| b.1.abs().partial_cmp(&a.1.abs()).unwrap());
+        print!("undetermined {}:", i);
+        for (c, a) in entries {
+            print!(" {:+.3} {}", a, labels[c]);
+        }
+        println!();
+    }
+}
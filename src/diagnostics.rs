@@ -0,0 +1,114 @@
+// Numerical health of a solve: how much precision the recipe matrix costs and how far
+// the solution moved
+
+use crate::gauss_newton::Scalar;
+
+/// 1-norm of a matrix: the largest absolute column sum
+pub fn norm1<T: Scalar>(a: &na::DMatrix<T>) -> T {
+    a.column_iter()
+        .map(|c| c.iter().fold(T::zero(), |sum, x| sum + x.abs()))
+        .fold(T::zero(), |max, sum| max.max(sum))
+}
+
+/// Estimate of the 1-norm condition number ‖A‖₁‖A⁻¹‖₁ of a square matrix by Hager's method
+/// (as in LAPACK's xGECON): a few solves with A and Aᵀ instead of computing A⁻¹.
+/// The estimate is a lower bound and usually exact within a factor of 3.
+/// Infinite for singular matrices.
+pub fn condition_estimate<T: Scalar>(a: &na::DMatrix<T>) -> T {
+    let n = a.nrows();
+    assert_eq!(n, a.ncols(), "condition_estimate needs a square matrix");
+    let lu = a.clone().lu();
+    let lu_t = a.transpose().lu();
+    if n == 0 {
+        return T::one();
+    }
+    if !lu.is_invertible() {
+        return T::max_value().unwrap();
+    }
+    let mut x = na::DVector::from_element(n, T::one() / na::convert(n as f64));
+    let mut estimate = T::zero();
+    for _ in 0..5 {
+        let y = lu.solve(&x).unwrap();
+        estimate = y.lp_norm(1);
+        let sign = y.map(|v| if v >= T::zero() { T::one() } else { -T::one() });
+        let z = lu_t.solve(&sign).unwrap();
+        let j = z.iamax();
+        if z[j].abs() <= z.dot(&x) {
+            break;
+        }
+        x = na::DVector::zeros(n);
+        x[j] = T::one();
+    }
+    norm1(a) * estimate
+}
+
+/// 2-norm condition number σmax/σmin from the SVD, also for rectangular matrices.
+/// Singular values below `tolerance` × σmax are not counted, the result is the condition of
+/// the well determined part together with its rank.
+pub fn condition_svd<T: Scalar>(a: &na::DMatrix<T>, tolerance: T) -> (T, usize) {
+    let sigma = a.clone().svd(false, false).singular_values;
+    let largest = sigma.max();
+    let rank = sigma.iter().filter(|&&s| s > largest * tolerance).count();
+    if rank == 0 {
+        return (T::one(), 0);
+    }
+    (largest / sigma[rank - 1], rank)
+}
+
+/// Summary of one labor redistribution
+#[derive(Clone, Debug)]
+pub struct Diagnostics<T> {
+    /// 2-norm condition of the recipe matrix (on its numerical rank)
+    pub condition: T,
+    /// numerical rank of the recipe matrix
+    pub rank: usize,
+    /// estimated 1-norm condition of the normal equations JᵀJ + λI the solver factorises
+    pub normal_condition: T,
+    /// ‖X β - y‖ after the solve
+    pub residual_norm: T,
+    /// ‖β - β_start‖ / ‖β_start‖
+    pub relative_change: T,
+    /// why the equations of this tick could not be dumped, see `Economy::dump`
+    pub dump_error: Option<String>,
+}
+
+impl<T: Scalar> Diagnostics<T> {
+    /// Roughly the number of correct decimal digits left in the solution of the normal
+    /// equations: log10(1 / (κ² ε)), where ε is the precision of T. The recipe condition is
+    /// used rather than `normal_condition`, which also counts the null space directions that
+    /// only the damping decides.
+    pub fn correct_digits(&self) -> T {
+        -(self.condition * self.condition * T::default_epsilon()).log10()
+    }
+
+    /// Fewer than three correct digits are left, switch to f64 or rescale the recipes
+    pub fn precision_at_risk(&self) -> bool {
+        let digits = na::try_convert::<T, f64>(self.correct_digits()).unwrap();
+        digits < 3.0 || digits.is_nan()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{condition_estimate, condition_svd, norm1};
+
+    #[test]
+    fn condition_numbers() {
+        let a = na::DMatrix::from_row_slice(2, 2, &[4.0, 1.0, 2.0, 3.0]);
+        assert_eq!(norm1(&a), 6.0);
+        // inverse is [3 -1; -2 4] / 10, so κ₁ = 6 * 0.5
+        let exact: f64 = 6.0 * 0.5;
+        assert!((condition_estimate(&a) - exact).abs() <= 1e-12);
+
+        let diagonal = na::DMatrix::from_row_slice(3, 2, &[1.0, 0.0, 0.0, 1e-3, 0.0, 0.0]);
+        let (kappa, rank) = condition_svd(&diagonal, 1e-6);
+        assert_eq!(rank, 2);
+        assert!((kappa - 1e3_f64).abs() <= 1e-9);
+        let (kappa, rank) = condition_svd(&diagonal, 1e-2);
+        assert_eq!(rank, 1);
+        assert_eq!(kappa, 1.0);
+
+        let singular = na::DMatrix::from_row_slice(2, 2, &[1.0, 1.0, 2.0, 2.0]);
+        assert_eq!(condition_estimate(&singular), f64::MAX);
+    }
+}
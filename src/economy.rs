@@ -0,0 +1,750 @@
+use crate::diagnostics::{condition_estimate, condition_svd, Diagnostics};
+use crate::gauss_newton::{
+    gauss_newton_refined, gauss_newton_robust, ApproximateInverse, Bounds, LineSearch, Loss,
+    Scalar, Session, StepStatus, BETA_K,
+};
+use crate::matrix_io;
+use crate::rs_leastsquare::{lexicographic_least_squares, pseudo_inverse, solve_auto, Truncation};
+
+use std::collections::BTreeMap as HashMap;
+use std::path::PathBuf;
+
+// use crate::rs_leastsquare::least_squares; //HashMap;
+
+// not possible, even in unstable???
+// const fn const_max<T: Ord+ Copy>(a: T, b: T) -> T {
+//     if a>b { a } else {b}
+// }
+
+pub const NUM_GOODS: usize = 4;
+pub const NUM_LABORS: usize = 5;
+const OVERPRODUCTION_TARGET: f32 = 1.01;
+// const NUM_MAX: usize = 5; //const_max::<usize>(NUM_GOODS,NUM_LABORS);
+
+// recipes and constants are given in f32, converted to the scalar type of the economy
+fn num<T: Scalar>(value: f32) -> T {
+    na::convert(value)
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum Good {
+    Log,  // Units: Kg
+    Wood, // Units Kg
+    Meat, // Units: Kg
+    Food,
+}
+
+pub const GOODS: [Good; NUM_GOODS] = [Good::Log, Good::Wood, Good::Meat, Good::Food];
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum Labor {
+    Lumberjack,
+    Carpenter,
+    Fisher,
+    Hunter,
+    Cook,
+}
+
+pub const LABORS: [Labor; NUM_LABORS] = [
+    Labor::Lumberjack,
+    Labor::Carpenter,
+    Labor::Fisher,
+    Labor::Hunter,
+    Labor::Cook,
+];
+
+impl Labor {
+    pub fn industry(&self) -> Industry<'static> {
+        match self {
+            Labor::Lumberjack => Industry {
+                inputs: &[],
+                outputs: &[(Good::Log, 10.0)],
+            },
+            Labor::Carpenter => Industry {
+                inputs: &[(Good::Log, 10.0)],
+                outputs: &[(Good::Wood, 10.0)], // 1/3rd is 'wasted' (sawdust, etc.)
+            },
+            Labor::Fisher => Industry {
+                inputs: &[(Good::Wood, 0.1)],
+                outputs: &[(Good::Meat, 1.0)],
+            },
+            Labor::Hunter => Industry {
+                inputs: &[],
+                outputs: &[(Good::Meat, 1.0)],
+            },
+            Labor::Cook => Industry {
+                inputs: &[(Good::Wood, 0.2), (Good::Meat, 1.0)],
+                outputs: &[(Good::Food, 1.0)], // Some fish is wasted (gutting)
+            },
+        }
+    }
+}
+
+// Goods used and made by one laborer per tick. Generated economies (supply_chain.rs) number
+// their goods instead of naming them.
+pub struct Industry<'a, G = Good> {
+    pub inputs: &'a [(G, f32)],
+    pub outputs: &'a [(G, f32)],
+}
+
+// How redistribute_laborers finds the new allocation
+pub enum Solver<T> {
+    // Loss::Huber etc. limit the influence of single goods with huge shortages
+    GaussNewton {
+        line_search: LineSearch<T>,
+        loss: Loss<T>,
+    },
+    // Goals grouped by priority: supply of the first group is optimized first, then the second
+    // group without making the first any worse, etc. Goods not listed are ignored. The result
+    // is clamped into the workplaces.
+    Lexicographic(Vec<Vec<Good>>),
+    // Minimum norm allocation from the pseudo inverse of the recipe matrix, ignores the current allocation
+    PseudoInverse(Truncation<T>),
+    // GaussNewton with mixed precision iterative refinement of every step, for f32 economies
+    // with badly scaled recipes
+    Refined(LineSearch<T>),
+    // The cheap approximate inverse of the supply chain while `equation minv` is within the
+    // tolerance of I, GaussNewton otherwise
+    Approximate(T),
+    // Unbounded least squares, the factorisation is picked from the shape and rank of the
+    // recipe matrix
+    Auto,
+}
+
+// Which ticks redistribute_laborers writes its x, y, beta_start and beta to `directory`, as
+// tick<n>_x.<format> etc. with the Good and Labor names as row and column labels
+pub struct Dump {
+    pub directory: PathBuf,
+    // counted from 0
+    pub ticks: Vec<u64>,
+    // file extension, mtx (the default) keeps all labels, see matrix_io::write_labeled
+    pub format: String,
+}
+
+impl Dump {
+    pub fn new(directory: impl Into<PathBuf>, ticks: Vec<u64>) -> Self {
+        Dump {
+            directory: directory.into(),
+            ticks,
+            format: "mtx".to_string(),
+        }
+    }
+}
+
+// f32 in the game, f64 for offline balancing runs
+pub struct Economy<T: Scalar = f32> {
+    // Economy population
+    pub pop: T,
+
+    // Number of laborers allocated to each industry
+    pub laborers: HashMap<Labor, T>,
+    // The relative productivity of each labor in the last tick
+    // 0.0 = At least one of the required input goods was not available
+    // 1.0 = All of the required input goods were available, sufficiently to saturate demand
+    // This is the minimum of the proportion that each input was supplied
+    pub productivity: HashMap<Labor, (T, Option<Good>)>,
+
+    // Given current workforce allocation, how much of each good will be produced on the next tick?
+    // This is expressed as a proportion of the total required for industry. i.e:
+    // >= 1.0 => supply completely saturates industry, oversupply
+    // <= 1.0 => supply is insufficient to satisfy industry, undersupply
+    pub available: HashMap<Good, T>,
+
+    // Labor value and consumption value are in the same units:
+    // - Labor value are the average number of labor hours required to produce 1 unit
+    // - Consumption values are the number of labor hours that workers would be willing to exchange for 1 unit
+    // During each tick, labor values are propagated forwards through the supply chain and consumption values are
+    // propagated backwards through the supply change, accounting for scarcity.
+    pub labor_value: HashMap<Good, T>,
+    // consumption_value: HashMap<Good, T>,
+
+    // The relative value of goods. Goods that are produced optimally are at 1.0 (i.e: labor value matches consumption value).
+    // > 1.0 => production of this good should increase
+    // < 1.0 => production of this good should reduce
+    // value: HashMap<Good, T>,
+    pub price: HashMap<Good, T>,
+
+    // Total output of this good that occured in the last tick
+    pub output: HashMap<Good, T>,
+
+    pub demand: HashMap<Good, T>,
+
+    // Number of workplaces of each industry, no more laborers can be allocated to it
+    pub workplaces: HashMap<Labor, T>,
+
+    pub solver: Solver<T>,
+    // Print the equations and solution of every redistribution
+    pub verbose: bool,
+    // Write the equations of some ticks to disk, None by default
+    pub dump: Option<Dump>,
+    // ticks done so far
+    ticks: u64,
+    // keeps the factorised recipe matrix from tick to tick
+    session: Session<T, NUM_GOODS, NUM_LABORS>,
+}
+
+fn my_print<T: Scalar, const M: usize, const N: usize>(
+    y: &nalgebra::SMatrix<T, M, 1>,
+    x: &nalgebra::SMatrix<T, M, N>,
+    beta: Option<&nalgebra::SMatrix<T, N, 1>>,
+) {
+    print!("\t\t");
+    if let Some(beta) = beta {
+        for j in 0..x.ncols() {
+            print!("{:.2}\t", beta[(j, 0)]);
+        }
+    }
+    println!();
+    for i in 0..x.nrows() {
+        print!("{:.2}\t\t", y[(i, 0)]);
+        for j in 0..x.ncols() {
+            print!("{:.3}\t", x[(i, j)]);
+        }
+        println!();
+    }
+}
+
+// fn newton(
+//     y: &nalgebra::DMatrix<f32>,
+//     x: &nalgebra::DMatrix<f32>,
+//     beta_start: &nalgebra::DMatrix<f32>,
+// ) -> nalgebra::DMatrix<f32> {
+//     let mut beta = beta_start.clone();
+//     let rows = beta.nrows();
+//     let f_x = x * beta.clone() - y;
+//     //.norm();
+//     //let norm = f_x.norm();
+//     //dbg!((&beta, &f_x));
+//     for i in 0..rows {
+//         let sum = x
+//             .row(i)
+//             .iter()
+//             .enumerate()
+//             .map(|(r, &val)| 2.0 * f_x[(r, 0)] * val)
+//             .sum::<f32>();
+//         let scale = f_x[(i, 0)].powi(2);
+//         dbg!((scale, sum, -scale / sum));
+//         beta[(i, 0)] -= scale / sum;
+//     }
+//     beta
+// }
+
+// fn gradient_descend(
+//     y: &nalgebra::DMatrix<f32>,
+//     x: &nalgebra::DMatrix<f32>,
+//     beta_start: &nalgebra::DMatrix<f32>,
+// ) -> nalgebra::DMatrix<f32> {
+//     let r = y - x * beta_start.clone();
+//     let r_t = r.transpose();
+//     let gamma1 = (r_t.clone() * r.clone())[(0, 0)];
+//     let gamma2 = (r_t.clone() * (x * r.clone()))[(0, 0)];
+//     dbg!(gamma1);
+//     dbg!(gamma2);
+//     let gamma = gamma1 / gamma2;
+//     dbg!(&r);
+//     dbg!(gamma);
+//     beta_start + gamma * r
+// }
+
+fn to_f64<T: Scalar, const M: usize, const N: usize>(m: &na::SMatrix<T, M, N>) -> na::DMatrix<f64> {
+    na::DMatrix::from_fn(M, N, |r, c| na::try_convert::<T, f64>(m[(r, c)]).unwrap())
+}
+
+impl<T: Scalar> Economy<T> {
+    pub fn new(pop: T) -> Self {
+        let mut economy = Economy {
+            pop,
+            laborers: HashMap::new(),
+            productivity: HashMap::new(),
+            available: HashMap::new(),
+            labor_value: HashMap::new(),
+            // consumption_value: HashMap::new(),
+            // value: HashMap::new(),
+            price: HashMap::new(),
+            output: HashMap::new(),
+            demand: HashMap::new(),
+            workplaces: HashMap::new(),
+            solver: Solver::GaussNewton {
+                line_search: LineSearch::Halving,
+                loss: Loss::Squared,
+            },
+            verbose: true,
+            dump: None,
+            ticks: 0,
+            session: Session::new(),
+        };
+
+        for labor in LABORS {
+            economy.laborers.insert(labor, T::one());
+            economy.workplaces.insert(labor, pop);
+        }
+        economy
+    }
+
+    // The recipe matrix used by redistribute_laborers: net output of each good (rows) per laborer
+    // of each industry (columns)
+    pub fn recipe_matrix(&self) -> na::SMatrix<T, NUM_GOODS, NUM_LABORS> {
+        let mut x = na::SMatrix::<T, NUM_GOODS, NUM_LABORS>::from_fn(|_n, _p| T::zero());
+        for p in 0..NUM_LABORS {
+            let labor = LABORS[p];
+            let products = labor.industry().outputs;
+            for (good, amount) in products {
+                let n = GOODS.iter().enumerate().find(|x| *x.1 == *good).unwrap().0;
+                //dbg!((n, p, amount, self.productivity[&labor].0, self.demand[good]));
+                x[(n, p)] = num(*amount); // / self.productivity[&labor].0.max(0.1) / self.demand[good];
+            }
+            let materials = labor.industry().inputs;
+            for (good, amount) in materials {
+                let n = GOODS.iter().enumerate().find(|x| *x.1 == *good).unwrap().0;
+                //dbg!((n, p, amount, self.productivity[&labor].0, self.demand[good]));
+                x[(n, p)] = -num::<T>(*amount); // / self.productivity[&labor].0.max(0.1) / self.demand[good];
+            }
+        }
+        x
+    }
+
+    // Calculate to what extent supply will satisfy demand for each good on the upcoming tick. See Economy::available.
+    fn derive_available_goods(&mut self) {
+        let mut total_demand = HashMap::new();
+        let mut total_supply = HashMap::new();
+
+        for labor in LABORS {
+            let industry = labor.industry();
+
+            let laborers = *self.laborers.get(&labor).unwrap_or(&T::zero());
+
+            // Productivity may limit goods that can be produced if inputs are undersupplied
+            // If 1.0, all industry inputs are satisfied. If 0.0, no industry inputs are satisfied.
+            let (limiting_good, productivity) = industry
+                .inputs
+                .iter()
+                // Productivity can never be lower than 0% or higher than 100%. You can throw capital at a tree as much
+                // as you like: labor is required for economic output!
+                .map(|(good, _)| {
+                    (
+                        Some(*good),
+                        self.available
+                            .get(good)
+                            .unwrap_or(&T::zero())
+                            .max(T::zero())
+                            .min(T::one()),
+                    )
+                })
+                .min_by(|(_, a), (_, b)| a.partial_cmp(b).unwrap())
+                .unwrap_or((None, T::one()));
+
+            for &(good, input) in industry.inputs {
+                *total_demand.entry(good).or_insert(T::zero()) += num::<T>(input) * laborers;
+            }
+
+            self.productivity
+                .insert(labor, (productivity, limiting_good));
+
+            for &(good, output) in industry.outputs {
+                //dbg!(&(good, output, laborers, productivity));
+                *total_supply.entry(good).or_insert(T::zero()) +=
+                    num::<T>(output) * laborers * productivity;
+            }
+        }
+
+        // TODO: determine required food based on consumption value & Maslow hierachy
+        total_demand.insert(Good::Food, self.pop * num(0.5));
+
+        for good in GOODS {
+            let total_supply = *total_supply.get(&good).unwrap_or(&T::zero());
+            let total_demand = *total_demand.get(&good).unwrap_or(&T::zero());
+            // println!("{:?}, total_supply = {}, total_demand = {}", good, total_supply, total_demand);
+            self.available
+                .insert(good, total_supply / total_demand.max(num(0.00001)));
+            self.price
+                .insert(good, total_demand / total_supply.max(num(0.00001)));
+            self.demand.insert(good, total_demand);
+        }
+    }
+
+    // Calculate labor values for each good by propagating its value forward through the supply chain (this is the easy
+    // part). The labor value of each good is simply the sum of the labor values of its inputs, in addition to the
+    // labor time required to create a unit of the input.
+    //
+    // Because more than one industry might produce the same good, we keep a running total of labour values vs outputs
+    // so that we can normalise this value across the industries afterwards.
+    fn derive_labor_values(&mut self) {
+        let mut total_labor_values = HashMap::<Good, T>::new();
+        let mut total_produced = HashMap::<Good, T>::new();
+
+        for labor in LABORS {
+            let industry = labor.industry();
+
+            let laborers = *self.laborers.get(&labor).unwrap_or(&T::zero());
+
+            let total_input_value = industry
+                .inputs
+                .iter()
+                .map(|(good, input)| {
+                    *self.labor_value.get(good).unwrap_or(&T::zero()) * num(*input)
+                })
+                .fold(T::zero(), |sum, value| sum + value);
+
+            let labor_time = T::one();
+
+            let productivity = self
+                .productivity
+                .get(&labor)
+                .unwrap_or(&(T::zero(), None))
+                .0;
+
+            for &(good, output) in industry.outputs {
+                let volume = num::<T>(output) * laborers * productivity;
+
+                *total_labor_values.entry(good).or_insert(T::zero()) +=
+                    (total_input_value + labor_time) / volume;
+                *total_produced.entry(good).or_insert(T::zero()) += volume;
+            }
+        }
+
+        for good in GOODS {
+            let total_labor_value = *total_labor_values.get(&good).unwrap_or(&T::zero());
+            let total_produced = *total_produced.get(&good).unwrap_or(&T::zero());
+            self.labor_value
+                .insert(good, total_labor_value / total_produced.max(num(0.00001)));
+
+            self.output.insert(good, total_produced);
+        }
+    }
+
+    fn redistribute_laborers(&mut self) -> Diagnostics<T> {
+        // minimize sum of ((supply-demand)/demand)²
+        // minimize sum of (supply/demand + BIAS)²
+        // with BIAS = -OVERPRODUCTION_TARGET, slightly towards overproduction
+
+        // supply = workers * amount * productivity
+        // so https://en.wikipedia.org/wiki/Ordinary_least_squares
+        // y = [-BIAS; N]
+        // X[n][p] = amount_np * productivity_p/ demand_n
+        // beta = laborers: [_;P]
+
+        let mut y =
+            na::SMatrix::<T, NUM_GOODS, 1>::from_fn(|_, _| num(OVERPRODUCTION_TARGET - 1.0));
+        y[3] = self.demand[&Good::Food];
+        let x = self.recipe_matrix();
+        // solve the under-determinism by making fisher and hunter scale by their efficiency
+        // x[(4, 2)] = -1.0;
+        // x[(4, 3)] = x[(2, 2)] / x[(2, 3)];
+        let beta_start = na::SMatrix::<T, NUM_LABORS, 1>::from_fn(|i, _| self.laborers[&LABORS[i]]);
+        let mut beta = beta_start;
+        let bounds = Bounds {
+            lower: na::SMatrix::<T, NUM_LABORS, 1>::zeros(),
+            upper: na::SMatrix::<T, NUM_LABORS, 1>::from_fn(|i, _| self.workplaces[&LABORS[i]]),
+        };
+        let verbose = self.verbose;
+        match &self.solver {
+            Solver::GaussNewton { line_search, loss } => {
+                for _ in 0..1 {
+                    let solution = match loss {
+                        Loss::Squared => {
+                            self.session
+                                .solve_bounded(&x, &y, &beta, &bounds, *line_search)
+                        }
+                        _ => gauss_newton_robust(&x, &y, &beta, &bounds, *line_search, *loss),
+                    };
+                    if verbose && solution.status == StepStatus::GaveUp {
+                        println!("Line search gave up, keeping the allocation");
+                    }
+                    beta = solution.x;
+                    if verbose {
+                        my_print(&y, &x, Some(&beta));
+                    }
+                }
+            }
+            Solver::Refined(line_search) => {
+                let (solution, refinement) =
+                    gauss_newton_refined(&x, &y, &beta, &bounds, *line_search);
+                if verbose {
+                    if let Some(refinement) = refinement {
+                        println!(
+                            "Refinement: residual {:.1e} -> {:.1e}, plain error {:.1e}, {} steps, {:.1} digits gained",
+                            refinement.plain_residual,
+                            refinement.residual,
+                            refinement.plain_error,
+                            refinement.iterations,
+                            refinement.digits_gained()
+                        );
+                    }
+                }
+                beta = solution.x;
+                if verbose {
+                    my_print(&y, &x, Some(&beta));
+                }
+            }
+            Solver::Approximate(tolerance) => {
+                let inverse = ApproximateInverse::new(&x);
+                if verbose {
+                    println!(
+                        "Approximate inverse: residual {:.3}, pseudo inverse error {:.3}",
+                        inverse.residual,
+                        inverse.pinv_error(&x)
+                    );
+                    for cycle in &inverse.non_productive {
+                        let goods: Vec<Good> = cycle.goods.iter().map(|g| GOODS[*g]).collect();
+                        println!(
+                            "Non-productive cycle {:?} with gain {:.2}",
+                            goods, cycle.gain
+                        );
+                    }
+                }
+                beta = if inverse.good_enough(*tolerance) {
+                    bounds.project(&inverse.solve(&x, &y, &beta, 10))
+                } else {
+                    self.session
+                        .solve_bounded(&x, &y, &beta, &bounds, LineSearch::Halving)
+                        .x
+                };
+                if verbose {
+                    my_print(&y, &x, Some(&beta));
+                }
+            }
+            Solver::Lexicographic(priorities) => {
+                let levels: Vec<_> = priorities
+                    .iter()
+                    .map(|goods| {
+                        let rows: Vec<usize> = goods
+                            .iter()
+                            .map(|good| GOODS.iter().position(|g| g == good).unwrap())
+                            .collect();
+                        (
+                            na::DMatrix::from_fn(rows.len(), NUM_LABORS, |r, c| x[(rows[r], c)]),
+                            na::DMatrix::from_fn(rows.len(), 1, |r, _| y[rows[r]]),
+                        )
+                    })
+                    .collect();
+                let start = na::DMatrix::from_fn(NUM_LABORS, 1, |r, _| beta_start[r]);
+                let solution = lexicographic_least_squares(&levels, &start, num(1e-5));
+                beta = bounds.project(&na::SMatrix::from_fn(|r, _| solution[r]));
+                if verbose {
+                    my_print(&y, &x, Some(&beta));
+                }
+            }
+            Solver::Auto => {
+                let solution = solve_auto(
+                    &na::DMatrix::from_fn(NUM_GOODS, NUM_LABORS, |r, c| x[(r, c)]),
+                    &na::DMatrix::from_fn(NUM_GOODS, 1, |r, _| y[r]),
+                    num(1e-5),
+                );
+                if verbose {
+                    println!("Solved with {:?} (rank {})", solution.method, solution.rank);
+                }
+                beta = na::SMatrix::<T, NUM_LABORS, 1>::from_fn(|r, _| solution.beta[r]);
+                if verbose {
+                    my_print(&y, &x, Some(&beta));
+                }
+            }
+            Solver::PseudoInverse(truncation) => {
+                let pinv = pseudo_inverse(
+                    &na::DMatrix::from_fn(NUM_GOODS, NUM_LABORS, |r, c| x[(r, c)]),
+                    *truncation,
+                );
+                beta = na::SMatrix::<T, NUM_LABORS, NUM_GOODS>::from_fn(|r, c| pinv[(r, c)]) * y;
+                if verbose {
+                    my_print(&y, &x, Some(&beta));
+                }
+            }
+        }
+        let mut diagnostics = self.diagnose(&x, &y, &beta_start, &beta);
+        if let Err(error) = self.dump(&x, &y, &beta_start, &beta) {
+            if verbose {
+                println!("Warning: could not dump tick {}: {}", self.ticks, error);
+            }
+            diagnostics.dump_error = Some(error.to_string());
+        }
+        if verbose && diagnostics.precision_at_risk() {
+            println!(
+                "Warning: recipe matrix is ill-conditioned ({:.1}), only {:.1} digits left",
+                diagnostics.condition,
+                diagnostics.correct_digits()
+            );
+        }
+        //for _ in 0..5 { beta = gradient_descend(&y, &x, &beta); my_print(&y, &x, Some(&beta)); }
+        // let beta = least_squares(&x, &y);
+        // my_print(&y, &x, beta.as_ref());
+
+        if true {
+            //let Some(beta) = beta {
+            for i in 0..NUM_LABORS {
+                if let Some(val) = self.laborers.get_mut(&LABORS[i]) {
+                    *val = beta[(i, 0)];
+                }
+            }
+        }
+
+        let working_pop = self.pop * T::one(); // For now, assume everybody in the economy can work (1.0)
+        let total_laborers = self
+            .laborers
+            .values()
+            .fold(T::zero(), |sum, laborers| sum + *laborers);
+
+        self.laborers.values_mut().for_each(|l| {
+            // This prevents any industry becoming completely drained of workforce, thereby inhibiting any production.
+            // Keeping a small number of laborers in every industry keeps things 'ticking over' so the economy can
+            // quickly adapt to changing conditions
+            let min_workforce_alloc = num(0.01);
+
+            let factor = if total_laborers > working_pop {
+                working_pop / total_laborers
+            } else {
+                T::one()
+            };
+            *l = (*l * factor).max(min_workforce_alloc);
+        });
+        diagnostics
+    }
+
+    fn dump(
+        &self,
+        x: &na::SMatrix<T, NUM_GOODS, NUM_LABORS>,
+        y: &na::SMatrix<T, NUM_GOODS, 1>,
+        beta_start: &na::SMatrix<T, NUM_LABORS, 1>,
+        beta: &na::SMatrix<T, NUM_LABORS, 1>,
+    ) -> std::io::Result<()> {
+        let dump = match &self.dump {
+            Some(dump) if dump.ticks.contains(&self.ticks) => dump,
+            _ => return Ok(()),
+        };
+        std::fs::create_dir_all(&dump.directory)?;
+        let goods: Vec<String> = GOODS.iter().map(|g| format!("{:?}", g)).collect();
+        let labors: Vec<String> = LABORS.iter().map(|l| format!("{:?}", l)).collect();
+        let write = |name: &str, m: na::DMatrix<f64>, rows: &[String], columns: &[String]| {
+            let path = dump
+                .directory
+                .join(format!("tick{}_{}.{}", self.ticks, name, dump.format));
+            matrix_io::write_labeled(path, &m, Some(rows), Some(columns))
+        };
+        let beta_column = ["beta".to_string()];
+        write("x", to_f64(x), &goods, &labors)?;
+        write("y", to_f64(y), &goods, &["y".to_string()])?;
+        write("beta_start", to_f64(beta_start), &labors, &beta_column)?;
+        write("beta", to_f64(beta), &labors, &beta_column)
+    }
+
+    fn diagnose(
+        &self,
+        x: &na::SMatrix<T, NUM_GOODS, NUM_LABORS>,
+        y: &na::SMatrix<T, NUM_GOODS, 1>,
+        beta_start: &na::SMatrix<T, NUM_LABORS, 1>,
+        beta: &na::SMatrix<T, NUM_LABORS, 1>,
+    ) -> Diagnostics<T> {
+        let dense = na::DMatrix::from_fn(NUM_GOODS, NUM_LABORS, |r, c| x[(r, c)]);
+        let (condition, rank) = condition_svd(&dense, T::default_epsilon() * num(NUM_GOODS as f32));
+        // the matrix gauss_newton factorises (up to the constant factor 2 of J)
+        let lambda: T = na::convert(BETA_K / 2.0);
+        let normal = dense.transpose() * &dense
+            + na::DMatrix::<T>::identity(NUM_LABORS, NUM_LABORS) * lambda;
+        Diagnostics {
+            condition,
+            rank,
+            normal_condition: condition_estimate(&normal),
+            residual_norm: (x * beta - y).norm(),
+            relative_change: (beta - beta_start).norm() / beta_start.norm().max(num(0.00001)),
+            dump_error: None,
+        }
+    }
+
+    pub fn tick(&mut self) -> Diagnostics<T> {
+        self.derive_available_goods();
+        self.derive_labor_values();
+        // self.derive_consumption_values();
+        // self.derive_values();
+        let diagnostics = self.redistribute_laborers();
+        self.ticks += 1;
+        diagnostics
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Dump, Economy, Good, Solver, GOODS, LABORS, NUM_GOODS, NUM_LABORS};
+    use crate::gauss_newton::{LineSearch, Loss};
+    use crate::matrix_io;
+
+    #[test]
+    fn approximate_solver() {
+        let run = |solver| {
+            let mut economy = Economy::<f64>::new(100.0);
+            economy.verbose = false;
+            economy.solver = solver;
+            for _ in 0..3 {
+                economy.tick();
+            }
+            economy.laborers
+        };
+        let exact = run(Solver::GaussNewton {
+            line_search: LineSearch::Halving,
+            loss: Loss::Squared,
+        });
+        // the recipes of the economy have an exact approximate inverse, it is used
+        let approximate = run(Solver::Approximate(1e-6));
+        for labor in LABORS {
+            let (a, b) = (approximate[&labor], exact[&labor]);
+            assert!((a - b).abs() <= 1e-3 * b, "{:?}: {} vs {}", labor, a, b);
+        }
+    }
+
+    #[test]
+    fn dump_reads_back() {
+        let directory = std::env::temp_dir().join(format!("economy3_dump_{}", std::process::id()));
+        let mut economy = Economy::<f64>::new(100.0);
+        economy.verbose = false;
+        economy.dump = Some(Dump::new(&directory, vec![1]));
+        economy.tick();
+        assert!(!directory.join("tick0_x.mtx").exists());
+        assert_eq!(economy.tick().dump_error, None);
+        let goods: Vec<String> = GOODS.iter().map(|g| format!("{:?}", g)).collect();
+        let labors: Vec<String> = LABORS.iter().map(|l| format!("{:?}", l)).collect();
+        let beta = vec!["beta".to_string()];
+        for (name, shape, rows, columns) in [
+            ("x", (NUM_GOODS, NUM_LABORS), &goods, &labors),
+            ("y", (NUM_GOODS, 1), &goods, &vec!["y".to_string()]),
+            ("beta_start", (NUM_LABORS, 1), &labors, &beta),
+            ("beta", (NUM_LABORS, 1), &labors, &beta),
+        ] {
+            let path = directory.join(format!("tick1_{}.mtx", name));
+            let (matrix, read_columns) = matrix_io::read_labeled(&path).unwrap();
+            assert_eq!(matrix.shape(), shape);
+            assert!(matrix.iter().all(|v| v.is_finite()));
+            assert_eq!(read_columns.as_ref(), Some(columns));
+            let text = std::fs::read_to_string(&path).unwrap();
+            let labels = matrix_io::matrix_market_labels(&text);
+            assert_eq!(labels, (Some(rows.clone()), Some(columns.clone())));
+        }
+
+        // a failed dump doesn't stop the economy but shows in the diagnostics
+        let blocked = directory.join("tick1_x.mtx");
+        economy.dump = Some(Dump::new(&blocked, vec![2]));
+        assert!(economy.tick().dump_error.is_some());
+        std::fs::remove_dir_all(&directory).unwrap();
+    }
+
+    #[test]
+    fn lexicographic_solver() {
+        let directory = std::env::temp_dir().join(format!("economy3_lex_{}", std::process::id()));
+        let mut economy = Economy::<f64>::new(100.0);
+        economy.verbose = false;
+        economy.solver = Solver::Lexicographic(vec![
+            vec![Good::Food],
+            vec![Good::Wood],
+            vec![Good::Log, Good::Meat],
+        ]);
+        // too few workplaces for the unbounded solution
+        for labor in LABORS {
+            economy.workplaces.insert(labor, 5.0);
+        }
+        economy.dump = Some(Dump::new(&directory, vec![0, 1, 2]));
+        for tick in 0..3 {
+            economy.tick();
+            let path = directory.join(format!("tick{}_beta.mtx", tick));
+            let beta = matrix_io::read(path).unwrap();
+            assert!(beta.iter().all(|&b| (0.0..=5.0).contains(&b)), "{}", beta);
+        }
+        assert!(economy.laborers.values().all(|l| l.is_finite()));
+        std::fs::remove_dir_all(&directory).unwrap();
+    }
+}
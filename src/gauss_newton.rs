@@ -1,122 +1,1230 @@
-use std::ptr::eq;
+use std::cmp::Ordering;
 
 use na::SMatrix;
-use rand::Rng;
+
+use crate::recipe_graph::{Cycle, RecipeGraph};
+use crate::rs_leastsquare::{pseudo_inverse, Truncation};
 
 // see https://en.wikipedia.org/wiki/Gauss%E2%80%93Newton_algorithm
 // and https://en.wikipedia.org/wiki/Levenberg%E2%80%93Marquardt_algorithm
 
 extern crate nalgebra as na;
 
-const beta_k: f32 = 0.001; // zero gives GaussNewton, non zero Levenberg-Marquardt
+pub(crate) const BETA_K: f64 = 0.001; // zero gives GaussNewton, non zero Levenberg-Marquardt
+
+const SQRT2: f64 = std::f64::consts::SQRT_2;
+
+/// Scalar type of the solvers: f32 in the game, f64 for offline balancing
+pub trait Scalar: na::RealField + Copy {}
 
-const sqrt2: f32 = 1.414213562_f32; // 2.0_f32.sqrt();
+impl<T: na::RealField + Copy> Scalar for T {}
 
-fn f<const M: usize, const N: usize>(
-    equation: &na::SMatrix<f32, M, N>,
-    bias: &na::SMatrix<f32, M, 1>,
-    x: &na::SMatrix<f32, N, 1>,
-) -> na::SMatrix<f32, M, 1> {
+// constant in the solver's scalar type
+fn lit<T: Scalar>(value: f64) -> T {
+    na::convert(value)
+}
+
+fn f<T: Scalar, const M: usize, const N: usize>(
+    equation: &na::SMatrix<T, M, N>,
+    bias: &na::SMatrix<T, M, 1>,
+    x: &na::SMatrix<T, N, 1>,
+) -> na::SMatrix<T, M, 1> {
     (equation * x) - bias
 }
 
 // df_r(x)/dx_c  (oh it is not dependent on x)
-fn J<const M: usize, const N: usize>(
-    equation: &na::SMatrix<f32, M, N>,
-    x: &na::SMatrix<f32, N, 1>,
-) -> na::SMatrix<f32, M, N> {
-    sqrt2 * equation
-}
-
-fn d<const M: usize, const N: usize>(
-    equation: &na::SMatrix<f32, M, N>,
-    bias: &na::SMatrix<f32, M, 1>,
-    x: &na::SMatrix<f32, N, 1>,
-) -> f32 {
+fn jacobian<T: Scalar, const M: usize, const N: usize>(
+    equation: &na::SMatrix<T, M, N>,
+    _x: &na::SMatrix<T, N, 1>,
+) -> na::SMatrix<T, M, N> {
+    equation * lit::<T>(SQRT2)
+}
+
+fn d<T: Scalar, const M: usize, const N: usize>(
+    equation: &na::SMatrix<T, M, N>,
+    bias: &na::SMatrix<T, M, 1>,
+    x: &na::SMatrix<T, N, 1>,
+) -> T {
     f(equation, bias, x).norm_squared()
 }
 
-fn print<const M: usize, const N: usize>(x: &nalgebra::SMatrix<f32, M, N>) {
-    for i in 0..M {
-        for j in 0..N {
-            print!("{:.3}\t", x[(i, j)]);
+/// Heuristic inverse of a supply chain matrix: the demand for each good is split over
+/// its producers by their share of the output, and the inputs those producers consume are
+/// followed through the chain (see `RecipeGraph::propagate`). Much cheaper than a pseudo
+/// inverse and close to it as long as every good has few producers.
+pub fn approximate_inverse<T: Scalar, const M: usize, const N: usize>(
+    x: &nalgebra::SMatrix<T, M, N>,
+) -> nalgebra::SMatrix<T, N, M> {
+    approximate_inverse_with_cycles(x).0
+}
+
+// with the non-productive cycles the propagation found
+fn approximate_inverse_with_cycles<T: Scalar, const M: usize, const N: usize>(
+    x: &nalgebra::SMatrix<T, M, N>,
+) -> (nalgebra::SMatrix<T, N, M>, Vec<Cycle<T>>) {
+    let propagation =
+        RecipeGraph::new(M, N, |r, c| x[(r, c)], |_| T::one(), T::zero()).propagate(N);
+    let minv = SMatrix::from_fn(|r, c| propagation.effort[c][r]);
+    (minv, propagation.non_productive)
+}
+
+/// Approximate inverse together with how good it is for `equation`
+pub struct ApproximateInverse<T, const M: usize, const N: usize> {
+    pub minv: na::SMatrix<T, N, M>,
+    /// cycles consuming more than they produce, their goods have no inverse and are left out
+    /// of minv
+    pub non_productive: Vec<Cycle<T>>,
+    /// ‖equation minv - I‖_F, below 1 `solve` converges
+    pub residual: T,
+}
+
+impl<T: Scalar, const M: usize, const N: usize> ApproximateInverse<T, M, N> {
+    pub fn new(equation: &na::SMatrix<T, M, N>) -> Self {
+        let (minv, non_productive) = approximate_inverse_with_cycles(equation);
+        let residual = (equation * minv - SMatrix::<T, M, M>::identity()).norm();
+        ApproximateInverse {
+            minv,
+            non_productive,
+            residual,
+        }
+    }
+
+    /// ‖minv - pinv‖_F / ‖pinv‖_F against the pseudo inverse of `equation`. Costs an SVD,
+    /// much more than the approximate inverse itself.
+    pub fn pinv_error(&self, equation: &na::SMatrix<T, M, N>) -> T {
+        let pinv = pseudo_inverse(
+            &na::DMatrix::from_fn(M, N, |r, c| equation[(r, c)]),
+            Truncation::Relative(T::default_epsilon() * lit(M.max(N) as f64)),
+        );
+        let pinv = SMatrix::<T, N, M>::from_fn(|r, c| pinv[(r, c)]);
+        (self.minv - pinv).norm() / pinv.norm().max(T::min_value().unwrap())
+    }
+
+    /// Good enough to replace the exact solvers, `equation minv` is within `tolerance` of I
+    pub fn good_enough(&self, tolerance: T) -> bool {
+        self.residual <= tolerance
+    }
+
+    /// Preconditioned Richardson iteration x += minv (bias - equation x) from `x0`.
+    /// Each step shrinks the residual by at least the factor `residual`.
+    pub fn solve(
+        &self,
+        equation: &na::SMatrix<T, M, N>,
+        bias: &na::SMatrix<T, M, 1>,
+        x0: &na::SMatrix<T, N, 1>,
+        iterations: u32,
+    ) -> na::SMatrix<T, N, 1> {
+        let mut x = *x0;
+        for _ in 0..iterations {
+            x += self.minv * (bias - equation * x);
         }
-        print!("\n");
+        x
     }
 }
 
-fn inv_recurse<const M: usize, const N: usize>(
-    res: &mut SMatrix<f32, N, M>,
-    x: &SMatrix<f32, M, N>,
-    row: usize,
-    destrow: usize,
-    factor: f32,
-    depth: u32,
-) {
-    let r = x.row(row);
-    let sum: f32 = r
-        .iter()
-        .filter_map(|a| if *a > 0.0 { Some(*a) } else { None })
-        .sum();
-    if sum > 0.0 {
-        let factor = factor / sum;
-        for (col, _) in r.iter().enumerate().filter(|(_, val)| **val > 0.0) {
-            res[(col, destrow)] += factor;
-            // println!("{} {} {} {}", depth, col, row, factor);
-            if depth >= 1 {
-                for (row2, value) in x.column(col).iter().enumerate().filter(|(_, v)| **v < 0.0) {
-                    inv_recurse(res, x, row2, destrow, -value * factor, depth - 1);
+/// How the length of a step along the Levenberg-Marquardt direction is chosen
+#[derive(Clone, Copy, Debug)]
+pub enum LineSearch<T> {
+    /// halve the step until the error drops
+    Halving,
+    /// backtrack until the sufficient decrease (Armijo) condition holds
+    Armijo { c1: T },
+    /// bracket and zoom until the strong Wolfe conditions hold
+    StrongWolfe { c1: T, c2: T },
+    /// keep the full step but raise the damping until the error drops (Marquardt)
+    TrustRegion,
+}
+
+/// Outcome of the step search
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum StepStatus<T> {
+    /// the step scaled by `alpha` and damped by `lambda` reduced the error
+    Accepted { alpha: T, lambda: T },
+    /// no reduction of the error was found, the start point is returned
+    GaveUp,
+}
+
+pub struct Solution<T, const N: usize> {
+    pub x: na::SMatrix<T, N, 1>,
+    pub error: T,
+    pub status: StepStatus<T>,
+}
+
+const MIN_ALPHA: f64 = 0.001;
+const MAX_SEARCH_STEPS: u32 = 20;
+const MAX_ITERATIONS: u32 = 100;
+// relative decrease of the error below which the bounded solvers stop
+const SETTLED: f64 = 1e-10;
+
+/// Lower and upper limit for each variable
+pub struct Bounds<T, const N: usize> {
+    pub lower: na::SMatrix<T, N, 1>,
+    pub upper: na::SMatrix<T, N, 1>,
+}
+
+impl<T: Scalar, const N: usize> Bounds<T, N> {
+    /// Clamp `x` into the box
+    pub fn project(&self, x: &na::SMatrix<T, N, 1>) -> na::SMatrix<T, N, 1> {
+        x.zip_zip_map(&self.lower, &self.upper, |x, l, u| x.max(l).min(u))
+    }
+}
+
+// solve (JᵀJ + λI) dvec = -Jᵀf, variables marked in `fixed` don't move. None if the damped
+// matrix is singular after all, λ is lost next to large entries of JᵀJ in f32.
+fn direction<T: Scalar, const N: usize>(
+    jtj: &na::SMatrix<T, N, N>,
+    gradient: &na::SMatrix<T, N, 1>,
+    fixed: &na::SMatrix<bool, N, 1>,
+    lambda: T,
+) -> Option<na::SMatrix<T, N, 1>> {
+    let damped = SMatrix::<T, N, N>::from_fn(|r, c| {
+        if fixed[r] || fixed[c] {
+            if r == c {
+                T::one()
+            } else {
+                T::zero()
+            }
+        } else if r == c {
+            jtj[(r, c)] + lambda
+        } else {
+            jtj[(r, c)]
+        }
+    });
+    let rhs = SMatrix::<T, N, 1>::from_fn(|r, _| if fixed[r] { T::zero() } else { gradient[r] });
+    damped.try_inverse().map(|inverse| -(inverse * rhs))
+}
+
+// error and its derivative along x + alpha * dvec (projected into the bounds)
+fn along<T: Scalar, const M: usize, const N: usize>(
+    equation: &na::SMatrix<T, M, N>,
+    bias: &na::SMatrix<T, M, 1>,
+    x: &na::SMatrix<T, N, 1>,
+    dvec: &na::SMatrix<T, N, 1>,
+    bounds: Option<&Bounds<T, N>>,
+    alpha: T,
+) -> (na::SMatrix<T, N, 1>, T, T) {
+    let unprojected = x + dvec * alpha;
+    let (x1, effective) = match bounds {
+        Some(bounds) => {
+            let x1 = bounds.project(&unprojected);
+            // clamped variables no longer move with alpha
+            let effective = dvec.zip_map(&(x1 - unprojected), |d, p| {
+                if p != T::zero() {
+                    T::zero()
+                } else {
+                    d
+                }
+            });
+            (x1, effective)
+        }
+        None => (unprojected, *dvec),
+    };
+    let f_x1 = f(equation, bias, &x1);
+    let slope = (equation * effective).dot(&f_x1) * lit(2.0);
+    (x1, f_x1.norm_squared(), slope)
+}
+
+// step lengths enclosing one that satisfies the strong Wolfe conditions, the error at `lo`
+// is the lower one
+struct Bracket<T> {
+    lo: T,
+    hi: T,
+    error_lo: T,
+}
+
+fn zoom<T: Scalar>(
+    phi: impl Fn(T) -> (T, T),
+    mut bracket: Bracket<T>,
+    error0: T,
+    slope0: T,
+    c1: T,
+    c2: T,
+) -> Option<T> {
+    for _ in 0..MAX_SEARCH_STEPS {
+        let Bracket { lo, hi, error_lo } = bracket;
+        let alpha = (lo + hi) * lit(0.5);
+        let (error, slope) = phi(alpha);
+        if error > error0 + c1 * alpha * slope0 || error >= error_lo {
+            bracket.hi = alpha;
+        } else {
+            if slope.abs() <= -c2 * slope0 {
+                return Some(alpha);
+            }
+            if slope * (hi - lo) >= T::zero() {
+                bracket.hi = lo;
+            }
+            bracket.lo = alpha;
+            bracket.error_lo = error;
+        }
+    }
+    None
+}
+
+// Nocedal & Wright, Numerical Optimization, algorithm 3.5
+fn strong_wolfe<T: Scalar>(
+    phi: impl Fn(T) -> (T, T),
+    error0: T,
+    slope0: T,
+    c1: T,
+    c2: T,
+) -> Option<T> {
+    let mut previous = T::zero();
+    let mut error_previous = error0;
+    let mut alpha = T::one();
+    for i in 0..MAX_SEARCH_STEPS {
+        let (error, slope) = phi(alpha);
+        if error > error0 + c1 * alpha * slope0 || (i > 0 && error >= error_previous) {
+            let bracket = Bracket {
+                lo: previous,
+                hi: alpha,
+                error_lo: error_previous,
+            };
+            return zoom(&phi, bracket, error0, slope0, c1, c2);
+        }
+        if slope.abs() <= -c2 * slope0 {
+            return Some(alpha);
+        }
+        if slope >= T::zero() {
+            let bracket = Bracket {
+                lo: alpha,
+                hi: previous,
+                error_lo: error,
+            };
+            return zoom(&phi, bracket, error0, slope0, c1, c2);
+        }
+        previous = alpha;
+        error_previous = error;
+        alpha *= lit(2.0);
+    }
+    None
+}
+
+// one damped Gauss-Newton step from x
+fn step<T: Scalar, const M: usize, const N: usize>(
+    equation: &na::SMatrix<T, M, N>,
+    bias: &na::SMatrix<T, M, 1>,
+    x: &na::SMatrix<T, N, 1>,
+    fixed: &na::SMatrix<bool, N, 1>,
+    bounds: Option<&Bounds<T, N>>,
+    line_search: LineSearch<T>,
+) -> Solution<T, N> {
+    let j = jacobian(equation, x);
+    let jt = j.transpose();
+    let jtj = jt * j;
+    let f_x = f(equation, bias, x);
+    let error0 = f_x.norm_squared();
+    let gradient = jt * f_x;
+    let gave_up = Solution {
+        x: *x,
+        error: error0,
+        status: StepStatus::GaveUp,
+    };
+    if let LineSearch::TrustRegion = line_search {
+        let mut lambda = lit(BETA_K);
+        for _ in 0..MAX_SEARCH_STEPS {
+            // a singular damped matrix may become regular with more damping
+            if let Some(dvec) = direction(&jtj, &gradient, fixed, lambda) {
+                let (x1, error1, _) = along(equation, bias, x, &dvec, bounds, T::one());
+                if error1 < error0 {
+                    return Solution {
+                        x: x1,
+                        error: error1,
+                        status: StepStatus::Accepted {
+                            alpha: T::one(),
+                            lambda,
+                        },
+                    };
                 }
             }
+            lambda *= lit(10.0);
         }
+        return gave_up;
+    }
+    match direction(&jtj, &gradient, fixed, lit(BETA_K)) {
+        Some(dvec) => search(equation, bias, x, &dvec, bounds, line_search, error0),
+        None => gave_up,
     }
 }
 
-fn my_inverse<const M: usize, const N: usize>(
-    x: &nalgebra::SMatrix<f32, M, N>,
-) -> nalgebra::SMatrix<f32, N, M> {
-    let mut res = SMatrix::zeros();
-    for row in 0..M {
-        inv_recurse(&mut res, &x, row, row, 1.0, 5);
-    }
-    res
-}
-
-pub fn gauss_newton<const M: usize, const N: usize>(
-    equation: &na::SMatrix<f32, M, N>,
-    bias: &na::SMatrix<f32, M, 1>,
-    x0: &na::SMatrix<f32, N, 1>,
-) -> na::SMatrix<f32, N, 1> {
-    let J = J(equation, x0);
-    let JT = J.transpose();
-    let I = SMatrix::<f32, N, N>::from_fn(|r, c| if r == c { 1.0 } else { 0.0 });
-    let D = JT.clone() * J.clone() + beta_k * I;
-    let Dinv = D.try_inverse().unwrap();
-    let f_x0 = f(equation, bias, x0);
-    let error0 = f_x0.norm_squared();
-    let dvec = -(Dinv * (JT * f_x0));
-    let scale2 = -(Dinv * JT);
-    print(&x0.transpose());
-    print(&f_x0.transpose());
-    print(&scale2);
-    print(&dvec.transpose());
-    print(&(scale2 * f_x0).transpose());
-    print(&equation);
-    let minv = my_inverse(&equation);
-    print(&(minv * -1.0 / sqrt2));
-    let mut alpha = 1.0_f32;
-    // line search
-    let x1 = loop {
-        let x1 = x0 + alpha * dvec.clone();
-        let f_x1 = f(equation, bias, &x1);
-        let error1 = f_x1.norm_squared();
-        if error1 < error0 {
-            break x1;
-        }
-        alpha /= 2.0;
-        if alpha < 0.001 {
-            break x0.clone(); // give up
+// pick the step length along dvec
+fn search<T: Scalar, const M: usize, const N: usize>(
+    equation: &na::SMatrix<T, M, N>,
+    bias: &na::SMatrix<T, M, 1>,
+    x: &na::SMatrix<T, N, 1>,
+    dvec: &na::SMatrix<T, N, 1>,
+    bounds: Option<&Bounds<T, N>>,
+    line_search: LineSearch<T>,
+    error0: T,
+) -> Solution<T, N> {
+    let gave_up = Solution {
+        x: *x,
+        error: error0,
+        status: StepStatus::GaveUp,
+    };
+    let phi = |alpha| {
+        let (_, error, slope) = along(equation, bias, x, dvec, bounds, alpha);
+        (error, slope)
+    };
+    let (_, slope0) = phi(T::zero());
+    // also rejects NaN
+    if slope0.partial_cmp(&T::zero()) != Some(Ordering::Less) {
+        return gave_up; // not a descent direction (already optimal)
+    }
+    let alpha = match line_search {
+        LineSearch::Halving | LineSearch::Armijo { .. } => {
+            let c1 = match line_search {
+                LineSearch::Armijo { c1 } => c1,
+                _ => T::zero(),
+            };
+            let mut alpha = T::one();
+            loop {
+                if phi(alpha).0 < error0 + c1 * alpha * slope0 {
+                    break Some(alpha);
+                }
+                alpha /= lit(2.0);
+                if alpha < lit(MIN_ALPHA) {
+                    break None;
+                }
+            }
+        }
+        LineSearch::StrongWolfe { c1, c2 } => strong_wolfe(phi, error0, slope0, c1, c2),
+        LineSearch::TrustRegion => unreachable!(),
+    };
+    match alpha {
+        Some(alpha) => {
+            let (x1, error1, _) = along(equation, bias, x, dvec, bounds, alpha);
+            Solution {
+                x: x1,
+                error: error1,
+                status: StepStatus::Accepted {
+                    alpha,
+                    lambda: lit(BETA_K),
+                },
+            }
         }
+        None => gave_up,
+    }
+}
+
+/// Levenberg-Marquardt with every iterate inside the box given by `bounds`.
+/// Variables sitting at a bound whose gradient points outwards are held fixed,
+/// the remaining step is projected back into the box during the line search.
+pub fn gauss_newton_bounded<T: Scalar, const M: usize, const N: usize>(
+    equation: &na::SMatrix<T, M, N>,
+    bias: &na::SMatrix<T, M, 1>,
+    x0: &na::SMatrix<T, N, 1>,
+    bounds: &Bounds<T, N>,
+    line_search: LineSearch<T>,
+) -> Solution<T, N> {
+    let x = bounds.project(x0);
+    let mut solution = Solution {
+        error: d(equation, bias, &x),
+        x,
+        status: StepStatus::GaveUp,
     };
-    x1
+    // each iteration can add or release one active bound, continue until the error settles
+    for _ in 0..MAX_ITERATIONS {
+        let gradient = jacobian(equation, &solution.x).transpose() * f(equation, bias, &solution.x);
+        let x = solution.x;
+        let active = na::SMatrix::<bool, N, 1>::from_fn(|r, _| {
+            (x[r] <= bounds.lower[r] && gradient[r] > T::zero())
+                || (x[r] >= bounds.upper[r] && gradient[r] < T::zero())
+        });
+        let next = step(equation, bias, &x, &active, Some(bounds), line_search);
+        if next.status == StepStatus::GaveUp {
+            break; // no further progress possible
+        }
+        let settled = solution.error - next.error <= solution.error * lit(SETTLED);
+        solution = next;
+        if settled {
+            break;
+        }
+    }
+    solution
+}
+
+/// Robust loss ρ applied to every residual, large residuals get less influence than with
+/// the squared error. The parameter is the residual scale where the loss starts to flatten.
+#[derive(Clone, Copy, Debug)]
+pub enum Loss<T> {
+    Squared,
+    Huber(T),
+    Cauchy(T),
+    SoftL1(T),
+}
+
+const MAX_IRLS_STEPS: u32 = 10;
+
+impl<T: Scalar> Loss<T> {
+    /// ρ(r), scaled to agree with r² for small residuals
+    pub fn rho(&self, r: T) -> T {
+        match *self {
+            Loss::Squared => r * r,
+            Loss::Huber(delta) => {
+                if r.abs() <= delta {
+                    r * r
+                } else {
+                    delta * (r.abs() * lit(2.0) - delta)
+                }
+            }
+            Loss::Cauchy(c) => c * c * (T::one() + (r / c).powi(2)).ln(),
+            Loss::SoftL1(c) => c * c * ((T::one() + (r / c).powi(2)).sqrt() - T::one()) * lit(2.0),
+        }
+    }
+
+    /// IRLS weight ρ'(r)/2r
+    pub fn weight(&self, r: T) -> T {
+        match *self {
+            Loss::Squared => T::one(),
+            Loss::Huber(delta) => {
+                if r.abs() <= delta {
+                    T::one()
+                } else {
+                    delta / r.abs()
+                }
+            }
+            Loss::Cauchy(c) => T::one() / (T::one() + (r / c).powi(2)),
+            Loss::SoftL1(c) => T::one() / (T::one() + (r / c).powi(2)).sqrt(),
+        }
+    }
+}
+
+/// Bounded Levenberg-Marquardt minimizing the sum of `loss` over the residuals,
+/// by iteratively reweighted least squares: each equation is scaled by the square root
+/// of its weight at the current residual and the weighted problem is solved again.
+pub fn gauss_newton_robust<T: Scalar, const M: usize, const N: usize>(
+    equation: &na::SMatrix<T, M, N>,
+    bias: &na::SMatrix<T, M, 1>,
+    x0: &na::SMatrix<T, N, 1>,
+    bounds: &Bounds<T, N>,
+    line_search: LineSearch<T>,
+    loss: Loss<T>,
+) -> Solution<T, N> {
+    let total_loss = |x: &na::SMatrix<T, N, 1>| {
+        f(equation, bias, x)
+            .iter()
+            .fold(T::zero(), |sum, r| sum + loss.rho(*r))
+    };
+    let mut solution = Solution {
+        x: bounds.project(x0),
+        error: T::zero(),
+        status: StepStatus::GaveUp,
+    };
+    let mut weights = na::SMatrix::<T, M, 1>::repeat(T::one());
+    for _ in 0..MAX_IRLS_STEPS {
+        let new_weights = f(equation, bias, &solution.x).map(|r| loss.weight(r));
+        let settled = (new_weights - weights).amax() < lit(1e-3);
+        weights = new_weights;
+        let scale = weights.map(T::sqrt);
+        let weighted_equation = SMatrix::<T, M, N>::from_fn(|r, c| scale[r] * equation[(r, c)]);
+        let weighted_bias = bias.component_mul(&scale);
+        let next = gauss_newton_bounded(
+            &weighted_equation,
+            &weighted_bias,
+            &solution.x,
+            bounds,
+            line_search,
+        );
+        if next.status == StepStatus::GaveUp {
+            break;
+        }
+        solution.x = next.x;
+        solution.status = next.status;
+        if settled {
+            break;
+        }
+    }
+    solution.error = total_loss(&solution.x);
+    solution
+}
+
+const MAX_REFINEMENTS: u32 = 10;
+
+/// How much iterative refinement improved one solve of the normal equations.
+/// Residuals are ‖Jᵀf + (JᵀJ + λI) dvec‖ / ‖Jᵀf‖, evaluated in f64 before `dvec` is
+/// rounded to T.
+pub struct Refinement<T, const N: usize> {
+    pub dvec: na::SMatrix<T, N, 1>,
+    /// residual of the plain solve in T, infinite if JᵀJ + λI rounded to T could not be
+    /// factorised and the direction was solved in f64
+    pub plain_residual: f64,
+    /// residual after refinement
+    pub residual: f64,
+    /// ‖dvec_plain - dvec‖ / ‖dvec‖, the error of the plain solve (infinite as above)
+    pub plain_error: f64,
+    pub iterations: u32,
+}
+
+impl<T: Scalar, const N: usize> Refinement<T, N> {
+    /// Decimal digits gained over the plain solve. The refined direction is exact up to the
+    /// rounding to T, so this compares the plain error with the precision of T.
+    pub fn digits_gained(&self) -> f64 {
+        let eps = na::try_convert::<T, f64>(T::default_epsilon()).unwrap();
+        (self.plain_error / eps).log10().max(0.0)
+    }
+}
+
+// exact widening of the solver's scalar type
+fn wide<T: Scalar, const R: usize, const C: usize>(
+    m: &na::SMatrix<T, R, C>,
+) -> na::SMatrix<f64, R, C> {
+    m.map(|v| na::try_convert::<T, f64>(v).unwrap())
+}
+
+// LM direction by mixed precision iterative refinement: JᵀJ + λI is formed in f64 and
+// factorised in T, the residual of each solve is computed in f64 and the correction is
+// solved with the same factor. Stops once the residual no longer halves. Variables marked
+// in `fixed` don't move, as in `direction`. None if JᵀJ + λI can't be factorised at all.
+fn refined_direction<T: Scalar, const M: usize, const N: usize>(
+    equation: &na::SMatrix<T, M, N>,
+    bias: &na::SMatrix<T, M, 1>,
+    x: &na::SMatrix<T, N, 1>,
+    fixed: &na::SMatrix<bool, N, 1>,
+) -> Option<Refinement<T, N>> {
+    let j = jacobian(&wide(equation), &wide(x));
+    let jtj = j.transpose() * j;
+    let gradient = j.transpose() * f(&wide(equation), &wide(bias), &wide(x));
+    let damped = SMatrix::<f64, N, N>::from_fn(|r, c| {
+        if fixed[r] || fixed[c] {
+            if r == c {
+                1.0
+            } else {
+                0.0
+            }
+        } else if r == c {
+            jtj[(r, c)] + BETA_K
+        } else {
+            jtj[(r, c)]
+        }
+    });
+    let rhs = SMatrix::<f64, N, 1>::from_fn(|r, _| if fixed[r] { 0.0 } else { -gradient[r] });
+    let rhs_norm = rhs.norm().max(f64::MIN_POSITIVE);
+    let residual_of = |dvec: &na::SMatrix<f64, N, 1>| rhs - damped * dvec;
+
+    let Some(factor) = na::Cholesky::new(damped.map(lit::<T>)) else {
+        // rounding to T broke positive definiteness, there is no plain solve to refine
+        let dvec = na::Cholesky::new(damped)?.solve(&rhs);
+        return Some(Refinement {
+            dvec: dvec.map(lit::<T>),
+            plain_residual: f64::INFINITY,
+            residual: residual_of(&dvec).norm() / rhs_norm,
+            plain_error: f64::INFINITY,
+            iterations: 0,
+        });
+    };
+    let plain = wide(&factor.solve(&rhs.map(lit::<T>)));
+    let mut dvec = plain;
+    let mut residual = residual_of(&dvec);
+    let plain_residual = residual.norm() / rhs_norm;
+    let mut iterations = 0;
+    while iterations < MAX_REFINEMENTS {
+        let correction = wide(&factor.solve(&residual.map(lit::<T>)));
+        let next = residual_of(&(dvec + correction));
+        if next.norm() > residual.norm() * 0.5 {
+            if next.norm() < residual.norm() {
+                dvec += correction;
+                residual = next;
+            }
+            break;
+        }
+        dvec += correction;
+        residual = next;
+        iterations += 1;
+    }
+    Some(Refinement {
+        dvec: dvec.map(lit::<T>),
+        plain_residual,
+        residual: residual.norm() / rhs_norm,
+        plain_error: (plain - dvec).norm() / dvec.norm().max(f64::MIN_POSITIVE),
+        iterations,
+    })
+}
+
+/// Like `gauss_newton_bounded`, but every direction is improved by iterative refinement
+/// so T = f32 gets close to f64 accuracy on ill-conditioned recipes.
+/// The refinement of the first step is returned with the solution, None if the recipes
+/// can't be factorised (e.g. NaN), then the start point is kept.
+/// `LineSearch::TrustRegion` would change the damping and is replaced by halving.
+pub fn gauss_newton_refined<T: Scalar, const M: usize, const N: usize>(
+    equation: &na::SMatrix<T, M, N>,
+    bias: &na::SMatrix<T, M, 1>,
+    x0: &na::SMatrix<T, N, 1>,
+    bounds: &Bounds<T, N>,
+    line_search: LineSearch<T>,
+) -> (Solution<T, N>, Option<Refinement<T, N>>) {
+    let line_search = match line_search {
+        LineSearch::TrustRegion => LineSearch::Halving,
+        other => other,
+    };
+    let x = bounds.project(x0);
+    let mut solution = Solution {
+        error: d(equation, bias, &x),
+        x,
+        status: StepStatus::GaveUp,
+    };
+    let mut first = None;
+    for _ in 0..MAX_ITERATIONS {
+        let gradient = jacobian(equation, &solution.x).transpose() * f(equation, bias, &solution.x);
+        let x = solution.x;
+        let active = na::SMatrix::<bool, N, 1>::from_fn(|r, _| {
+            (x[r] <= bounds.lower[r] && gradient[r] > T::zero())
+                || (x[r] >= bounds.upper[r] && gradient[r] < T::zero())
+        });
+        let Some(refinement) = refined_direction(equation, bias, &x, &active) else {
+            break;
+        };
+        let next = search(
+            equation,
+            bias,
+            &x,
+            &refinement.dvec,
+            Some(bounds),
+            line_search,
+            solution.error,
+        );
+        first.get_or_insert(refinement);
+        if next.status == StepStatus::GaveUp {
+            break;
+        }
+        let settled = solution.error - next.error <= solution.error * lit(SETTLED);
+        solution = next;
+        if settled {
+            break;
+        }
+    }
+    (solution, first)
+}
+
+/// Keeps the Cholesky factorisation of JᵀJ + λI between solves. As long as the equation
+/// matrix stays the same (recipes and productivity did not change) new targets and start
+/// points are solved without factorising again.
+pub struct Session<T: Scalar, const M: usize, const N: usize> {
+    equation: na::SMatrix<T, M, N>,
+    factor: Option<na::Cholesky<T, na::Const<N>>>,
+    /// number of factorisations so far
+    pub factorizations: u32,
+}
+
+impl<T: Scalar, const M: usize, const N: usize> Default for Session<T, M, N> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<T: Scalar, const M: usize, const N: usize> Session<T, M, N> {
+    pub fn new() -> Self {
+        Session {
+            equation: na::SMatrix::zeros(),
+            factor: None,
+            factorizations: 0,
+        }
+    }
+
+    // None if JᵀJ + λI can't be factorised, e.g. for NaN or infinite recipes
+    fn factor(
+        &mut self,
+        equation: &na::SMatrix<T, M, N>,
+    ) -> Option<&na::Cholesky<T, na::Const<N>>> {
+        if self.factorizations == 0 || self.equation != *equation {
+            let j = jacobian(equation, &na::SMatrix::zeros());
+            let damped = j.transpose() * j + SMatrix::<T, N, N>::identity() * lit::<T>(BETA_K);
+            self.factor = if damped.iter().all(|v| v.is_finite()) {
+                na::Cholesky::new(damped)
+            } else {
+                None
+            };
+            self.equation = *equation;
+            self.factorizations += 1;
+        }
+        self.factor.as_ref()
+    }
+
+    /// Solves the same problem as `gauss_newton_bounded`. While the full steps stay inside
+    /// the bounds no bound is active and the cached factorisation is used. Once a step would
+    /// leave the box `gauss_newton_bounded` takes over from the current point with its active
+    /// set, as it does with `LineSearch::TrustRegion` (which changes the damping) and for an
+    /// equation that can't be factorised. Where the damped matrix is singular it stops at
+    /// the current point.
+    pub fn solve_bounded(
+        &mut self,
+        equation: &na::SMatrix<T, M, N>,
+        bias: &na::SMatrix<T, M, 1>,
+        x0: &na::SMatrix<T, N, 1>,
+        bounds: &Bounds<T, N>,
+        line_search: LineSearch<T>,
+    ) -> Solution<T, N> {
+        if let LineSearch::TrustRegion = line_search {
+            return gauss_newton_bounded(equation, bias, x0, bounds, line_search);
+        }
+        let x = bounds.project(x0);
+        let mut solution = Solution {
+            error: d(equation, bias, &x),
+            x,
+            status: StepStatus::GaveUp,
+        };
+        for _ in 0..MAX_ITERATIONS {
+            let f_x = f(equation, bias, &solution.x);
+            let gradient = jacobian(equation, &solution.x).transpose() * f_x;
+            let dvec = self.factor(equation).map(|factor| -factor.solve(&gradient));
+            let dvec = match dvec {
+                Some(dvec) if bounds.project(&(solution.x + dvec)) == solution.x + dvec => dvec,
+                _ => {
+                    let mut bounded =
+                        gauss_newton_bounded(equation, bias, &solution.x, bounds, line_search);
+                    if bounded.status == StepStatus::GaveUp {
+                        bounded.status = solution.status;
+                    }
+                    return bounded;
+                }
+            };
+            let next = search(
+                equation,
+                bias,
+                &solution.x,
+                &dvec,
+                None,
+                line_search,
+                solution.error,
+            );
+            if next.status == StepStatus::GaveUp {
+                break;
+            }
+            let settled = solution.error - next.error <= solution.error * lit(SETTLED);
+            solution = next;
+            if settled {
+                break;
+            }
+        }
+        solution
+    }
+
+    /// `solve_bounded` for every column of `biases`, e.g. demand scenarios, each starting
+    /// from the matching column of `x0`. The equation is factorised once and shared by all
+    /// of them, except that a scenario whose steps reach a bound continues with
+    /// `gauss_newton_bounded`, which factorises for every step.
+    pub fn solve_bounded_many<const K: usize>(
+        &mut self,
+        equation: &na::SMatrix<T, M, N>,
+        biases: &na::SMatrix<T, M, K>,
+        x0: &na::SMatrix<T, N, K>,
+        bounds: &Bounds<T, N>,
+        line_search: LineSearch<T>,
+    ) -> Vec<Solution<T, N>> {
+        (0..K)
+            .map(|k| {
+                self.solve_bounded(
+                    equation,
+                    &biases.fixed_columns::<1>(k).into_owned(),
+                    &x0.fixed_columns::<1>(k).into_owned(),
+                    bounds,
+                    line_search,
+                )
+            })
+            .collect()
+    }
+}
+
+/// One of many small independent problems, e.g. the allocation of one site
+pub struct Problem<T, const M: usize, const N: usize> {
+    pub equation: na::SMatrix<T, M, N>,
+    pub bias: na::SMatrix<T, M, 1>,
+    pub x0: na::SMatrix<T, N, 1>,
+    pub bounds: Bounds<T, N>,
+}
+
+/// `Session::solve_bounded` for a batch of problems. Problems with the same equation
+/// (sites with the same recipes and productivity) share one session and its factorisation,
+/// as long as their steps stay inside the bounds.
+pub fn gauss_newton_batch<T: Scalar, const M: usize, const N: usize>(
+    problems: &[Problem<T, M, N>],
+    line_search: LineSearch<T>,
+) -> Vec<Solution<T, N>> {
+    let mut sessions: Vec<Session<T, M, N>> = Vec::new();
+    problems
+        .iter()
+        .map(|problem| {
+            let index = match sessions
+                .iter()
+                .position(|session| session.equation == problem.equation)
+            {
+                Some(index) => index,
+                None => {
+                    sessions.push(Session::new());
+                    sessions.len() - 1
+                }
+            };
+            sessions[index].solve_bounded(
+                &problem.equation,
+                &problem.bias,
+                &problem.x0,
+                &problem.bounds,
+                line_search,
+            )
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{
+        approximate_inverse, d, gauss_newton_batch, gauss_newton_bounded, gauss_newton_refined,
+        gauss_newton_robust, refined_direction, search, step, strong_wolfe, ApproximateInverse,
+        Bounds, LineSearch, Loss, Problem, Session, StepStatus,
+    };
+    use crate::recipe_graph::RecipeGraph;
+    use crate::supply_chain::Generator;
+
+    #[test]
+    fn bounded() {
+        // x0 + x1 = 4 and x1 = 3 want x = (1, 3), but x1 may not exceed 2
+        let equation = na::SMatrix::<f64, 2, 2>::new(1.0, 1.0, 0.0, 1.0);
+        let bias = na::SMatrix::<f64, 2, 1>::new(4.0, 3.0);
+        let bounds = Bounds {
+            lower: na::SMatrix::<f64, 2, 1>::zeros(),
+            upper: na::SMatrix::<f64, 2, 1>::new(10.0, 2.0),
+        };
+        let inside = |x: &na::SMatrix<f64, 2, 1>| bounds.project(x) == *x;
+
+        // the full step leaves the box, the accepted one is projected back
+        let start = na::SMatrix::<f64, 2, 1>::new(0.5, 0.5);
+        let fixed = na::SMatrix::<bool, 2, 1>::repeat(false);
+        let next = step(
+            &equation,
+            &bias,
+            &start,
+            &fixed,
+            Some(&bounds),
+            LineSearch::Halving,
+        );
+        assert!(matches!(next.status, StepStatus::Accepted { .. }));
+        assert!(inside(&next.x));
+
+        for x0 in [start, na::SMatrix::<f64, 2, 1>::new(-3.0, 7.0)] {
+            let solution =
+                gauss_newton_bounded(&equation, &bias, &x0, &bounds, LineSearch::Halving);
+            assert!(inside(&solution.x));
+            // x1 is pinned at its upper bound, x0 minimizes the rest:
+            // (x0 + 2 - 4)² + (2 - 3)² is smallest at x0 = 2
+            assert_eq!(solution.x[1], 2.0);
+            assert!((solution.x[0] - 2.0).abs() <= 1e-4);
+            assert!((solution.error - 1.0).abs() <= 1e-8);
+            // the free variable is optimal, the pinned one still pushes against its bound
+            let gradient = equation.transpose() * (equation * solution.x - bias);
+            assert!(gradient[0].abs() <= 1e-4);
+            assert!(gradient[1] < 0.0);
+        }
+
+        // both variables pushed below zero end up clamped there
+        let bias = na::SMatrix::<f64, 2, 1>::new(-4.0, -3.0);
+        let solution = gauss_newton_bounded(&equation, &bias, &start, &bounds, LineSearch::Halving);
+        assert_eq!(solution.x, na::SMatrix::<f64, 2, 1>::zeros());
+    }
+
+    #[test]
+    fn line_searches() {
+        // along the line the error is (alpha - 3)², a step of 1 is too short
+        let phi = |alpha: f64| ((alpha - 3.0).powi(2), 2.0 * (alpha - 3.0));
+        let (c1, c2) = (1e-4, 0.1);
+        let alpha = strong_wolfe(phi, 9.0, -6.0, c1, c2).unwrap();
+        let (error, slope) = phi(alpha);
+        assert!(error <= 9.0 - c1 * alpha * 6.0);
+        assert!(slope.abs() <= c2 * 6.0);
+        // overshooting, the minimum has to be zoomed in on
+        let phi = |alpha: f64| ((alpha - 0.3).powi(2), 2.0 * (alpha - 0.3));
+        let alpha = strong_wolfe(phi, 0.09, -0.6, c1, c2).unwrap();
+        assert!(phi(alpha).1.abs() <= c2 * 0.6);
+
+        // overdetermined, least squares solution x = (1, 2)
+        let equation = na::SMatrix::<f64, 3, 2>::new(1.0, 0.0, 0.0, 1.0, 1.0, 1.0);
+        let bias = na::SMatrix::<f64, 3, 1>::new(1.5, 2.5, 2.5);
+        let bounds = Bounds {
+            lower: na::SMatrix::<f64, 2, 1>::repeat(-100.0),
+            upper: na::SMatrix::<f64, 2, 1>::repeat(100.0),
+        };
+        let x0 = na::SMatrix::<f64, 2, 1>::new(5.0, -5.0);
+        for line_search in [
+            LineSearch::Halving,
+            LineSearch::Armijo { c1: 1e-4 },
+            LineSearch::StrongWolfe { c1: 1e-4, c2: 0.9 },
+            LineSearch::TrustRegion,
+        ] {
+            let solution = gauss_newton_bounded(&equation, &bias, &x0, &bounds, line_search);
+            assert!(
+                (solution.x - na::SMatrix::<f64, 2, 1>::new(1.0, 2.0)).amax() <= 1e-4,
+                "{:?}",
+                line_search
+            );
+            assert!((solution.error - 0.75).abs() <= 1e-8);
+        }
+    }
+
+    #[test]
+    fn gave_up() {
+        let equation = na::SMatrix::<f64, 2, 2>::new(1.0, 0.0, 0.0, 2.0);
+        let bias = na::SMatrix::<f64, 2, 1>::new(1.0, 2.0);
+        let fixed = na::SMatrix::<bool, 2, 1>::repeat(false);
+        // nothing to improve at the minimum
+        let x = na::SMatrix::<f64, 2, 1>::new(1.0, 1.0);
+        for line_search in [LineSearch::Halving, LineSearch::TrustRegion] {
+            let solution = step(&equation, &bias, &x, &fixed, None, line_search);
+            assert_eq!(solution.status, StepStatus::GaveUp);
+            assert_eq!(solution.x, x);
+        }
+        // uphill the start point is kept
+        let x = na::SMatrix::<f64, 2, 1>::zeros();
+        let uphill = na::SMatrix::<f64, 2, 1>::new(-1.0, -1.0);
+        let solution = search(
+            &equation,
+            &bias,
+            &x,
+            &uphill,
+            None,
+            LineSearch::Halving,
+            5.0,
+        );
+        assert_eq!((solution.status, solution.x), (StepStatus::GaveUp, x));
+    }
+
+    #[test]
+    fn singular_in_f32() {
+        // JᵀJ is rank one with entries of 4·10⁴, λ disappears when added to them in f32
+        let equation = na::SMatrix::<f32, 2, 2>::repeat(100.0);
+        let bias = na::SMatrix::<f32, 2, 1>::new(1.0, 2.0);
+        let x = na::SMatrix::<f32, 2, 1>::new(1.0, 1.0);
+        let fixed = na::SMatrix::<bool, 2, 1>::repeat(false);
+        let solution = step(&equation, &bias, &x, &fixed, None, LineSearch::Halving);
+        assert_eq!((solution.status, solution.x), (StepStatus::GaveUp, x));
+        // more damping makes it regular again
+        let solution = step(&equation, &bias, &x, &fixed, None, LineSearch::TrustRegion);
+        assert!(solution.error < d(&equation, &bias, &x));
+        let bounds = Bounds {
+            lower: na::SMatrix::<f32, 2, 1>::zeros(),
+            upper: na::SMatrix::<f32, 2, 1>::repeat(10.0),
+        };
+        let solution = gauss_newton_bounded(&equation, &bias, &x, &bounds, LineSearch::Halving);
+        assert_eq!((solution.status, solution.x), (StepStatus::GaveUp, x));
+    }
+
+    #[test]
+    fn outlier() {
+        // y = 1 + 2t sampled at t = 0..6, the last sample is off by 20
+        let equation =
+            na::SMatrix::<f64, 6, 2>::from_fn(|r, c| if c == 0 { 1.0 } else { r as f64 });
+        let mut bias = na::SMatrix::<f64, 6, 1>::from_fn(|r, _| 1.0 + 2.0 * r as f64);
+        bias[5] += 20.0;
+        let bounds = Bounds {
+            lower: na::SMatrix::<f64, 2, 1>::repeat(-100.0),
+            upper: na::SMatrix::<f64, 2, 1>::repeat(100.0),
+        };
+        let line = na::SMatrix::<f64, 2, 1>::new(1.0, 2.0);
+        let x0 = na::SMatrix::<f64, 2, 1>::zeros();
+        let solve =
+            |loss| gauss_newton_robust(&equation, &bias, &x0, &bounds, LineSearch::Halving, loss);
+        let plain = solve(Loss::Squared);
+        let plain_error = (plain.x - line).norm();
+        assert!(plain_error > 1.0);
+        for loss in [Loss::Huber(1.0), Loss::Cauchy(1.0), Loss::SoftL1(1.0)] {
+            let robust = solve(loss);
+            let residual = equation * robust.x - bias;
+            // the outlier gets much less weight than the other samples
+            assert!(
+                loss.weight(residual[5]) < 0.2 * loss.weight(residual[0]),
+                "{:?}",
+                loss
+            );
+            assert!((robust.x - line).norm() < 0.5 * plain_error, "{:?}", loss);
+        }
+    }
+
+    #[test]
+    fn session() {
+        let equation = na::SMatrix::<f64, 3, 2>::new(1.0, 0.0, 0.0, 1.0, 1.0, 1.0);
+        let bounds = Bounds {
+            lower: na::SMatrix::<f64, 2, 1>::zeros(),
+            upper: na::SMatrix::<f64, 2, 1>::repeat(10.0),
+        };
+        let x0 = na::SMatrix::<f64, 2, 1>::repeat(1.0);
+        let mut session = Session::new();
+        for bias in [[1.5, 2.5, 2.5], [3.0, 1.0, 4.0], [1.0, 1.0, 3.0]] {
+            let bias = na::SMatrix::<f64, 3, 1>::from_column_slice(&bias);
+            let cached = session.solve_bounded(&equation, &bias, &x0, &bounds, LineSearch::Halving);
+            let uncached =
+                gauss_newton_bounded(&equation, &bias, &x0, &bounds, LineSearch::Halving);
+            assert!((cached.x - uncached.x).amax() <= 1e-4);
+        }
+        assert_eq!(session.factorizations, 1);
+
+        // a bound is hit, the active set takes over
+        let bias = na::SMatrix::<f64, 3, 1>::new(-2.0, 1.0, -1.0);
+        let solution = session.solve_bounded(&equation, &bias, &x0, &bounds, LineSearch::Halving);
+        assert_eq!(solution.x[0], 0.0);
+        assert!((solution.x[1] - 0.0).abs() <= 1e-4);
+        assert_eq!(session.factorizations, 1);
+
+        let other = equation * 2.0;
+        session.solve_bounded(&other, &bias, &x0, &bounds, LineSearch::Halving);
+        session.solve_bounded(&other, &bias, &x0, &bounds, LineSearch::Halving);
+        assert_eq!(session.factorizations, 2);
+
+        // no factorisation, and no panic, for broken recipes
+        let mut broken = equation;
+        broken[(0, 0)] = f64::NAN;
+        let solution = session.solve_bounded(&broken, &bias, &x0, &bounds, LineSearch::Halving);
+        assert_eq!((solution.x, solution.status), (x0, StepStatus::GaveUp));
+
+        // nor for a rank deficient f32 equation whose damping is lost in rounding
+        let equation = na::SMatrix::<f32, 2, 2>::repeat(100.0);
+        let bias = na::SMatrix::<f32, 2, 1>::new(1.0, 2.0);
+        let x0 = na::SMatrix::<f32, 2, 1>::repeat(1.0);
+        let bounds = Bounds {
+            lower: na::SMatrix::<f32, 2, 1>::zeros(),
+            upper: na::SMatrix::<f32, 2, 1>::repeat(10.0),
+        };
+        let solution =
+            Session::new().solve_bounded(&equation, &bias, &x0, &bounds, LineSearch::Halving);
+        assert_eq!(bounds.project(&solution.x), solution.x);
+        assert!(solution.error <= d(&equation, &bias, &x0));
+    }
+
+    #[test]
+    fn refinement() {
+        // nearly dependent columns, JᵀJ + λI has a condition of about 10⁶. The f64 reference
+        // solves the same (rounded) problem.
+        let equation = na::SMatrix::<f32, 3, 2>::new(10.0, 10.0, 10.0, 10.01, 10.0, 9.99);
+        let bias = na::SMatrix::<f32, 3, 1>::new(1.0, 2.0, 3.0);
+        let x = na::SMatrix::<f32, 2, 1>::zeros();
+        let wide_equation = equation.cast::<f64>();
+        let wide_bias = bias.cast::<f64>();
+        let free = na::SMatrix::<bool, 2, 1>::repeat(false);
+        let exact = refined_direction(&wide_equation, &wide_bias, &x.cast(), &free)
+            .unwrap()
+            .dvec;
+        let narrow = refined_direction(&equation, &bias, &x, &free).unwrap();
+        let error = (narrow.dvec.cast::<f64>() - exact).norm() / exact.norm();
+        assert!(error <= 1e-6);
+        assert!(narrow.plain_error >= 1e3 * error);
+        assert!(narrow.residual < narrow.plain_residual);
+        assert!(narrow.digits_gained() >= 1.0);
+
+        // a fixed variable doesn't move
+        let fixed = na::SMatrix::<bool, 2, 1>::new(false, true);
+        let narrow = refined_direction(&equation, &bias, &x, &fixed).unwrap();
+        assert_eq!(narrow.dvec[1], 0.0);
+
+        // the whole solve in f32 with an active bound agrees with f64
+        let bounds = Bounds {
+            lower: na::SMatrix::<f32, 2, 1>::zeros(),
+            upper: na::SMatrix::<f32, 2, 1>::new(1.0, 0.1),
+        };
+        let (solution, first) =
+            gauss_newton_refined(&equation, &bias, &x, &bounds, LineSearch::Halving);
+        assert!(first.is_some());
+        assert!(bounds.project(&solution.x) == solution.x);
+        let wide_bounds = Bounds {
+            lower: bounds.lower.cast(),
+            upper: bounds.upper.cast(),
+        };
+        let reference = gauss_newton_bounded(
+            &wide_equation,
+            &wide_bias,
+            &x.cast(),
+            &wide_bounds,
+            LineSearch::Halving,
+        );
+        assert!((solution.x.cast::<f64>() - reference.x).amax() <= 1e-4);
+
+        // JᵀJ + λI is singular once rounded to f32, the direction is solved in f64
+        let equation = na::SMatrix::<f32, 2, 2>::repeat(100.0);
+        let bias = na::SMatrix::<f32, 2, 1>::new(1.0, 2.0);
+        let x = na::SMatrix::<f32, 2, 1>::zeros();
+        let refinement = refined_direction(&equation, &bias, &x, &free).unwrap();
+        assert!(refinement.dvec.iter().all(|v| v.is_finite()));
+        assert!(refinement.residual <= 1e-6);
+    }
+
+    #[test]
+    fn many_and_batch() {
+        let equation = na::SMatrix::<f64, 3, 2>::new(1.0, 0.0, 0.0, 1.0, 1.0, 1.0);
+        let bounds = || Bounds {
+            lower: na::SMatrix::<f64, 2, 1>::zeros(),
+            upper: na::SMatrix::<f64, 2, 1>::repeat(10.0),
+        };
+        // the last scenario runs into the lower bound of x0
+        let biases = na::SMatrix::<f64, 3, 3>::new(1.5, 3.0, -2.0, 2.5, 1.0, 1.0, 2.5, 4.0, -1.0);
+        let x0 = na::SMatrix::<f64, 2, 3>::repeat(1.0);
+        let mut session = Session::new();
+        let many =
+            session.solve_bounded_many(&equation, &biases, &x0, &bounds(), LineSearch::Halving);
+        assert_eq!(session.factorizations, 1);
+        assert_eq!(many.len(), 3);
+        for (k, solution) in many.iter().enumerate() {
+            let single = gauss_newton_bounded(
+                &equation,
+                &biases.column(k).into_owned(),
+                &x0.column(k).into_owned(),
+                &bounds(),
+                LineSearch::Halving,
+            );
+            assert!((solution.x - single.x).amax() <= 1e-4);
+        }
+        assert_eq!(many[2].x[0], 0.0);
+
+        // two sites share their recipes, the third has twice the productivity
+        let problems: Vec<_> = [(equation, 0), (equation * 2.0, 1), (equation, 2)]
+            .into_iter()
+            .map(|(equation, k)| Problem {
+                equation,
+                bias: biases.column(k).into_owned(),
+                x0: x0.column(k).into_owned(),
+                bounds: bounds(),
+            })
+            .collect();
+        let batch = gauss_newton_batch(&problems, LineSearch::Halving);
+        assert_eq!(batch.len(), 3);
+        for (problem, solution) in problems.iter().zip(&batch) {
+            let single = gauss_newton_bounded(
+                &problem.equation,
+                &problem.bias,
+                &problem.x0,
+                &problem.bounds,
+                LineSearch::Halving,
+            );
+            assert!((solution.x - single.x).amax() <= 1e-4);
+        }
+        assert!((batch[0].x - many[0].x).amax() <= 1e-12);
+    }
+
+    #[test]
+    fn approximate() {
+        // one producer per good, cycles included: the inverse is exact
+        let chain = Generator {
+            goods: 30,
+            duplicates: 0.0,
+            cycles: 0.3,
+            ..Generator::default()
+        }
+        .generate(3);
+        let x = chain.smatrix::<30, 30>().cast::<f64>();
+        let graph = RecipeGraph::new(30, 30, |r, c| x[(r, c)], |_| 1.0, 0.0);
+        assert!(!graph.cycles().is_empty());
+        let inverse = ApproximateInverse::new(&x);
+        assert!(inverse.non_productive.is_empty());
+        assert!(
+            (x * approximate_inverse(&x) - na::SMatrix::<f64, 30, 30>::identity()).norm() <= 1e-10
+        );
+        assert!(inverse.residual <= 1e-10);
+        assert!(inverse.pinv_error(&x) <= 1e-8);
+
+        // labor 0 also makes half a unit of good 1, whose demand is split over both labors
+        let x = na::SMatrix::<f64, 2, 2>::new(1.0, 0.0, 0.5, 1.0);
+        let inverse = ApproximateInverse::new(&x);
+        let residual = (x * inverse.minv - na::SMatrix::<f64, 2, 2>::identity()).norm();
+        assert!((inverse.residual - residual).abs() <= 1e-12);
+        assert!(residual > 0.1 && residual < 1.0);
+        assert!(!inverse.good_enough(0.1) && inverse.good_enough(1.0));
+        // Richardson iteration still converges to the solution
+        let bias = na::SMatrix::<f64, 2, 1>::new(2.0, 3.0);
+        let solution = inverse.solve(&x, &bias, &na::SMatrix::zeros(), 60);
+        assert!((x * solution - bias).norm() <= 1e-8);
+    }
 }
@@ -0,0 +1,12 @@
+// shared between the economy and the experiments in src/bin
+extern crate nalgebra as na;
+
+pub mod bench;
+pub mod diagnostics;
+pub mod economy;
+pub mod gauss_newton;
+pub mod matrix_io;
+pub mod recipe_graph;
+pub mod rs_leastsquare;
+pub mod sparse;
+pub mod supply_chain;
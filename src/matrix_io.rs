@@ -0,0 +1,428 @@
+// Matrix files shared between the Rust binaries and the Python scripts:
+// MatrixMarket (.mtx), CSV (.csv) and numpy (.npy)
+
+use std::io;
+use std::path::Path;
+
+fn invalid(message: impl Into<String>) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, message.into())
+}
+
+/// Reads a matrix, the format is taken from the extension (.mtx, .npy, anything else is CSV)
+pub fn read(path: impl AsRef<Path>) -> io::Result<na::DMatrix<f64>> {
+    read_labeled(path).map(|(matrix, _)| matrix)
+}
+
+/// Like `read`, with the column labels of a CSV header or a MatrixMarket `% columns:` comment
+pub fn read_labeled(path: impl AsRef<Path>) -> io::Result<(na::DMatrix<f64>, Option<Vec<String>>)> {
+    let path = path.as_ref();
+    match extension(path).as_str() {
+        "mtx" => {
+            let text = std::fs::read_to_string(path)?;
+            let (_, columns) = matrix_market_labels(&text);
+            parse_matrix_market(&text).map(|m| (m, columns))
+        }
+        "npy" => parse_npy(&std::fs::read(path)?).map(|m| (m, None)),
+        _ => parse_csv(&std::fs::read_to_string(path)?),
+    }
+}
+
+/// Writes a matrix, the format is taken from the extension as in `read`
+pub fn write(path: impl AsRef<Path>, matrix: &na::DMatrix<f64>) -> io::Result<()> {
+    write_labeled(path, matrix, None, None)
+}
+
+/// Like `write`, with row and column labels. MatrixMarket keeps both, CSV only the column
+/// labels and `.npy` none.
+pub fn write_labeled(
+    path: impl AsRef<Path>,
+    matrix: &na::DMatrix<f64>,
+    rows: Option<&[String]>,
+    columns: Option<&[String]>,
+) -> io::Result<()> {
+    let path = path.as_ref();
+    match extension(path).as_str() {
+        "mtx" => std::fs::write(path, to_matrix_market_labeled(matrix, rows, columns)),
+        "npy" => std::fs::write(path, to_npy(matrix)),
+        _ => std::fs::write(path, to_csv(matrix, columns)),
+    }
+}
+
+fn extension(path: &Path) -> String {
+    path.extension()
+        .and_then(|e| e.to_str())
+        .unwrap_or("")
+        .to_ascii_lowercase()
+}
+
+/// One row per line, entries separated by commas or whitespace. An optional line starting
+/// with `#` names the columns.
+pub fn parse_csv(text: &str) -> io::Result<(na::DMatrix<f64>, Option<Vec<String>>)> {
+    let mut labels = None;
+    let mut rows = Vec::new();
+    for line in text.lines().map(str::trim).filter(|l| !l.is_empty()) {
+        let fields = line
+            .trim_start_matches('#')
+            .split(|c: char| c == ',' || c.is_whitespace())
+            .filter(|f| !f.is_empty());
+        if line.starts_with('#') {
+            labels = Some(fields.map(String::from).collect());
+        } else {
+            rows.push(
+                fields
+                    .map(|f| {
+                        f.parse::<f64>()
+                            .map_err(|_| invalid(format!("not a number: {}", f)))
+                    })
+                    .collect::<io::Result<Vec<_>>>()?,
+            );
+        }
+    }
+    let ncols = rows.first().map_or(0, Vec::len);
+    if rows.iter().any(|r| r.len() != ncols) {
+        return Err(invalid("ragged matrix"));
+    }
+    let matrix = na::DMatrix::from_fn(rows.len(), ncols, |r, c| rows[r][c]);
+    Ok((matrix, labels))
+}
+
+/// Comma separated, with a `#` header line if there are labels
+pub fn to_csv(matrix: &na::DMatrix<f64>, labels: Option<&[String]>) -> String {
+    let mut text = String::new();
+    if let Some(labels) = labels {
+        text += &format!("# {}\n", labels.join(","));
+    }
+    for row in matrix.row_iter() {
+        let fields: Vec<String> = row.iter().map(|v| v.to_string()).collect();
+        text += &fields.join(",");
+        text.push('\n');
+    }
+    text
+}
+
+/// MatrixMarket `coordinate` (sparse) or `array` (dense, column major) files with real or
+/// integer entries, `general` or `symmetric`
+pub fn parse_matrix_market(text: &str) -> io::Result<na::DMatrix<f64>> {
+    let mut lines = text.lines();
+    let banner = lines.next().unwrap_or("").to_ascii_lowercase();
+    let banner: Vec<&str> = banner.split_whitespace().collect();
+    if banner.len() != 5 || banner[0] != "%%matrixmarket" || banner[1] != "matrix" {
+        return Err(invalid("not a MatrixMarket matrix"));
+    }
+    let coordinate = match banner[2] {
+        "coordinate" => true,
+        "array" => false,
+        other => return Err(invalid(format!("unknown MatrixMarket format {}", other))),
+    };
+    if banner[3] != "real" && banner[3] != "integer" {
+        return Err(invalid(format!("unsupported entries {}", banner[3])));
+    }
+    let symmetric = match banner[4] {
+        "general" => false,
+        "symmetric" => true,
+        other => return Err(invalid(format!("unsupported symmetry {}", other))),
+    };
+    let mut numbers = lines
+        .map(str::trim)
+        .filter(|l| !l.is_empty() && !l.starts_with('%'))
+        .flat_map(str::split_whitespace);
+    let mut next = |what: &str| -> io::Result<&str> {
+        numbers
+            .next()
+            .ok_or_else(|| invalid(format!("missing {}", what)))
+    };
+    let index = |field: &str| -> io::Result<usize> {
+        field
+            .parse()
+            .map_err(|_| invalid(format!("not an index: {}", field)))
+    };
+    let value = |field: &str| -> io::Result<f64> {
+        field
+            .parse()
+            .map_err(|_| invalid(format!("not a number: {}", field)))
+    };
+    let nrows = index(next("size")?)?;
+    let ncols = index(next("size")?)?;
+    // a broken size line should not abort on allocation
+    let len = nrows
+        .checked_mul(ncols)
+        .ok_or_else(|| invalid("MatrixMarket size too large"))?;
+    let mut data = Vec::new();
+    data.try_reserve_exact(len)
+        .map_err(|_| invalid("MatrixMarket size too large"))?;
+    data.resize(len, 0.0);
+    let mut matrix = na::DMatrix::from_vec(nrows, ncols, data);
+    if coordinate {
+        let nnz = index(next("size")?)?;
+        for _ in 0..nnz {
+            let (r, c) = (index(next("row")?)?, index(next("column")?)?);
+            if r == 0 || c == 0 || r > nrows || c > ncols {
+                return Err(invalid(format!("entry ({}, {}) outside the matrix", r, c)));
+            }
+            let v = value(next("value")?)?;
+            matrix[(r - 1, c - 1)] = v;
+            if symmetric {
+                matrix[(c - 1, r - 1)] = v;
+            }
+        }
+    } else {
+        for c in 0..ncols {
+            // symmetric arrays only store the lower triangle
+            for r in (if symmetric { c } else { 0 })..nrows {
+                let v = value(next("value")?)?;
+                matrix[(r, c)] = v;
+                if symmetric {
+                    matrix[(c, r)] = v;
+                }
+            }
+        }
+    }
+    Ok(matrix)
+}
+
+/// MatrixMarket `coordinate real general`, only the nonzero entries
+pub fn to_matrix_market(matrix: &na::DMatrix<f64>) -> String {
+    to_matrix_market_labeled(matrix, None, None)
+}
+
+/// Like `to_matrix_market`, with the labels in `% rows:` and `% columns:` comments, which
+/// other readers skip. Labels must not contain whitespace.
+pub fn to_matrix_market_labeled(
+    matrix: &na::DMatrix<f64>,
+    rows: Option<&[String]>,
+    columns: Option<&[String]>,
+) -> String {
+    let mut text = String::from("%%MatrixMarket matrix coordinate real general\n");
+    if let Some(rows) = rows {
+        text += &format!("% rows: {}\n", rows.join(" "));
+    }
+    if let Some(columns) = columns {
+        text += &format!("% columns: {}\n", columns.join(" "));
+    }
+    let entries: Vec<(usize, usize, f64)> = (0..matrix.nrows())
+        .flat_map(|r| (0..matrix.ncols()).map(move |c| (r, c)))
+        .map(|(r, c)| (r, c, matrix[(r, c)]))
+        .filter(|(_, _, v)| *v != 0.0)
+        .collect();
+    text += &format!("{} {} {}\n", matrix.nrows(), matrix.ncols(), entries.len());
+    for (r, c, v) in entries {
+        text += &format!("{} {} {}\n", r + 1, c + 1, v);
+    }
+    text
+}
+
+/// The row and column labels written by `to_matrix_market_labeled`
+pub fn matrix_market_labels(text: &str) -> (Option<Vec<String>>, Option<Vec<String>>) {
+    let labels = |key: &str| {
+        text.lines()
+            .take_while(|l| l.starts_with('%'))
+            .find_map(|l| l.trim_start_matches('%').trim().strip_prefix(key))
+            .map(|l| l.split_whitespace().map(String::from).collect())
+    };
+    (labels("rows:"), labels("columns:"))
+}
+
+const NPY_MAGIC: &[u8] = b"\x93NUMPY";
+
+/// numpy `.npy` with one or two dimensions, little endian float64/float32/int64/int32,
+/// C or Fortran order. A vector becomes a column.
+pub fn parse_npy(bytes: &[u8]) -> io::Result<na::DMatrix<f64>> {
+    if bytes.len() < 10 || &bytes[..6] != NPY_MAGIC {
+        return Err(invalid("not a .npy file"));
+    }
+    let (header_len, start) = match bytes[6] {
+        1 => (u16::from_le_bytes([bytes[8], bytes[9]]) as usize, 10),
+        2 | 3 if bytes.len() >= 12 => (
+            u32::from_le_bytes([bytes[8], bytes[9], bytes[10], bytes[11]]) as usize,
+            12,
+        ),
+        version => return Err(invalid(format!("unsupported .npy version {}", version))),
+    };
+    let header = bytes
+        .get(start..start + header_len)
+        .and_then(|h| std::str::from_utf8(h).ok())
+        .ok_or_else(|| invalid("broken .npy header"))?;
+    let field = |key: &str| {
+        header
+            .find(&format!("'{}':", key))
+            .map(|i| header[i + key.len() + 3..].trim_start())
+            .ok_or_else(|| invalid(format!(".npy header without {}", key)))
+    };
+    let descr = field("descr")?;
+    let descr = descr
+        .strip_prefix('\'')
+        .and_then(|d| d.find('\'').map(|end| &d[..end]))
+        .unwrap_or("");
+    let fortran_order = field("fortran_order")?.starts_with("True");
+    let shape: Vec<usize> = field("shape")?
+        .strip_prefix('(')
+        .and_then(|s| s.find(')').map(|end| &s[..end]))
+        .ok_or_else(|| invalid("broken .npy shape"))?
+        .split(',')
+        .map(str::trim)
+        .filter(|s| !s.is_empty())
+        .map(|s| s.parse().map_err(|_| invalid("broken .npy shape")))
+        .collect::<io::Result<_>>()?;
+    let (nrows, ncols) = match shape[..] {
+        [n] => (n, 1),
+        [r, c] => (r, c),
+        _ => return Err(invalid("only vectors and matrices are supported")),
+    };
+    let size = match descr {
+        "<f8" | "<i8" => 8,
+        "<f4" | "<i4" => 4,
+        other => return Err(invalid(format!("unsupported .npy type {}", other))),
+    };
+    let len = nrows
+        .checked_mul(ncols)
+        .and_then(|n| n.checked_mul(size))
+        .ok_or_else(|| invalid(".npy shape too large"))?;
+    let data = &bytes[start + header_len..];
+    if data.len() < len {
+        return Err(invalid("truncated .npy data"));
+    }
+    let element = |i: usize| -> f64 {
+        let b = &data[i * size..(i + 1) * size];
+        match descr {
+            "<f8" => f64::from_le_bytes(b.try_into().unwrap()),
+            "<i8" => i64::from_le_bytes(b.try_into().unwrap()) as f64,
+            "<f4" => f32::from_le_bytes(b.try_into().unwrap()) as f64,
+            _ => i32::from_le_bytes(b.try_into().unwrap()) as f64,
+        }
+    };
+    Ok(if fortran_order {
+        na::DMatrix::from_fn(nrows, ncols, |r, c| element(c * nrows + r))
+    } else {
+        na::DMatrix::from_fn(nrows, ncols, |r, c| element(r * ncols + c))
+    })
+}
+
+/// numpy `.npy` version 1.0, float64 in C order
+pub fn to_npy(matrix: &na::DMatrix<f64>) -> Vec<u8> {
+    let mut header = format!(
+        "{{'descr': '<f8', 'fortran_order': False, 'shape': ({}, {}), }}",
+        matrix.nrows(),
+        matrix.ncols()
+    );
+    // the data starts at a multiple of 64 bytes, the header ends with a newline
+    while !(NPY_MAGIC.len() + 4 + header.len() + 1).is_multiple_of(64) {
+        header.push(' ');
+    }
+    header.push('\n');
+    let mut bytes = NPY_MAGIC.to_vec();
+    bytes.extend_from_slice(&[1, 0]);
+    bytes.extend_from_slice(&(header.len() as u16).to_le_bytes());
+    bytes.extend_from_slice(header.as_bytes());
+    for row in matrix.row_iter() {
+        for v in row.iter() {
+            bytes.extend_from_slice(&v.to_le_bytes());
+        }
+    }
+    bytes
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{
+        matrix_market_labels, parse_csv, parse_matrix_market, parse_npy, to_csv, to_matrix_market,
+        to_matrix_market_labeled, to_npy,
+    };
+
+    #[test]
+    fn round_trips() {
+        let x = na::DMatrix::from_row_slice(2, 3, &[1.5, 0.0, -2.0, 0.0, 1e-7, 71.646]);
+
+        let labels = vec![
+            "Farmer".to_string(),
+            "Fisher".to_string(),
+            "Cook".to_string(),
+        ];
+        let (csv, read_labels) = parse_csv(&to_csv(&x, Some(&labels))).unwrap();
+        assert_eq!(csv, x);
+        assert_eq!(read_labels, Some(labels.clone()));
+
+        let mtx = to_matrix_market(&x);
+        assert!(mtx.starts_with("%%MatrixMarket matrix coordinate real general\n2 3 4\n"));
+        assert_eq!(parse_matrix_market(&mtx).unwrap(), x);
+        let rows = vec!["Log".to_string(), "Food".to_string()];
+        let mtx = to_matrix_market_labeled(&x, Some(&rows), Some(&labels));
+        assert_eq!(parse_matrix_market(&mtx).unwrap(), x);
+        assert_eq!(matrix_market_labels(&mtx), (Some(rows), Some(labels)));
+        assert_eq!(matrix_market_labels(&to_matrix_market(&x)), (None, None));
+
+        let npy = to_npy(&x);
+        assert!((npy.len() - 2 * 3 * 8).is_multiple_of(64));
+        assert_eq!(parse_npy(&npy).unwrap(), x);
+    }
+
+    #[test]
+    fn foreign_files() {
+        let dense = "%%MatrixMarket matrix array real general\n% comment\n2 2\n1\n2\n3\n4\n";
+        let x = parse_matrix_market(dense).unwrap();
+        assert_eq!(x, na::DMatrix::from_row_slice(2, 2, &[1.0, 3.0, 2.0, 4.0]));
+        let symmetric = "%%MatrixMarket matrix coordinate integer symmetric\n2 2 2\n1 1 5\n2 1 7\n";
+        let x = parse_matrix_market(symmetric).unwrap();
+        assert_eq!(x, na::DMatrix::from_row_slice(2, 2, &[5.0, 7.0, 7.0, 0.0]));
+
+        // np.save of np.arange(3, dtype=np.int64), a vector
+        let mut npy = b"\x93NUMPY\x01\x00".to_vec();
+        let header = "{'descr': '<i8', 'fortran_order': False, 'shape': (3,), }";
+        let header = format!("{:<width$}\n", header, width = 64 - 10 - 1);
+        npy.extend_from_slice(&(header.len() as u16).to_le_bytes());
+        npy.extend_from_slice(header.as_bytes());
+        for i in 0..3i64 {
+            npy.extend_from_slice(&i.to_le_bytes());
+        }
+        assert_eq!(
+            parse_npy(&npy).unwrap(),
+            na::DMatrix::from_column_slice(3, 1, &[0.0, 1.0, 2.0])
+        );
+        assert!(parse_npy(b"not numpy").is_err());
+        assert!(parse_csv("1,2\n3").is_err());
+    }
+
+    #[test]
+    fn malformed_matrix_market() {
+        let mtx = |size: &str| {
+            parse_matrix_market(&format!(
+                "%%MatrixMarket matrix coordinate real general\n{} 0\n",
+                size
+            ))
+        };
+        assert_eq!(mtx("2 3").unwrap(), na::DMatrix::zeros(2, 3));
+        // more elements than usize holds, more bytes than it holds, more than memory holds
+        assert!(mtx("99999999999 99999999999").is_err());
+        assert!(mtx("4000000000 4000000000").is_err());
+        assert!(mtx("1000000000 1000000000").is_err());
+        assert!(mtx("-1 3").is_err());
+    }
+
+    #[test]
+    fn malformed_npy() {
+        let npy = |header: &str| {
+            let mut npy = b"\x93NUMPY\x01\x00".to_vec();
+            npy.extend_from_slice(&(header.len() as u16).to_le_bytes());
+            npy.extend_from_slice(header.as_bytes());
+            npy.extend_from_slice(&[0; 16]);
+            parse_npy(&npy)
+        };
+        assert!(npy("{'descr': '<f8', 'fortran_order': False, 'shape': (2,), }").is_ok());
+        // descr empty, not quoted, not closed or not ASCII
+        assert!(npy("{'fortran_order': False, 'shape': (2,), 'descr':").is_err());
+        assert!(npy("{'descr': <f8, 'fortran_order': False, 'shape': (2,), }").is_err());
+        assert!(npy("{'fortran_order': False, 'shape': (2,), 'descr': '<f8").is_err());
+        assert!(npy("{'descr': 'é', 'fortran_order': False, 'shape': (2,), }").is_err());
+        assert!(npy("{'descr': é, 'fortran_order': False, 'shape': (2,), }").is_err());
+        // shape empty, not a tuple or too large to address
+        assert!(npy("{'descr': '<f8', 'fortran_order': False, 'shape':").is_err());
+        assert!(npy("{'descr': '<f8', 'fortran_order': False, 'shape': )(, }").is_err());
+        let huge = format!("({}, {})", usize::MAX / 2, 3);
+        let header = format!(
+            "{{'descr': '<f8', 'fortran_order': False, 'shape': {}, }}",
+            huge
+        );
+        assert!(npy(&header).is_err());
+        // more data than the file has
+        assert!(npy("{'descr': '<f8', 'fortran_order': False, 'shape': (3,), }").is_err());
+    }
+}
@@ -0,0 +1,414 @@
+// Goods connected by the labors producing them: a good needs the inputs of every labor
+// producing it. Used to propagate labor effort through supply chains with feedback loops.
+
+use std::cmp::Ordering;
+
+use rayon::prelude::*;
+
+use crate::gauss_newton::Scalar;
+
+const GAIN_ITERATIONS: u32 = 100;
+// gains this close to 1 can't be told apart from 1 by the power iteration, I - A is singular
+// or nearly so
+const GAIN_TOLERANCE: f64 = 1e-9;
+
+/// One unit of good `g` needs `direct[g]` (labor, laborers) and `needs[g]` (good, units)
+pub struct RecipeGraph<T> {
+    pub direct: Vec<Vec<(usize, T)>>,
+    pub needs: Vec<Vec<(usize, T)>>,
+}
+
+/// Goods which need each other, directly or through other goods
+#[derive(Clone, Debug)]
+pub struct Cycle<T> {
+    pub goods: Vec<usize>,
+    /// units of each good the cycle consumes to make one unit of it (spectral radius of the
+    /// needs within the cycle), 1 or more means it can't produce anything
+    pub gain: T,
+}
+
+/// Labor effort per unit of each good
+pub struct Propagation<T> {
+    /// effort[good][labor], the goods of a non-productive cycle are treated as not produced
+    /// (zero effort, like goods below the threshold)
+    pub effort: Vec<Vec<T>>,
+    pub non_productive: Vec<Cycle<T>>,
+}
+
+impl<T: Scalar> RecipeGraph<T> {
+    /// From a recipe matrix with a row per good and a column per labor, positive entries are
+    /// output and negative ones input. The demand for a good is split over its producers by
+    /// their share of the output (amount × productivity), goods whose total output is not
+    /// above `threshold` are treated as not produced.
+    pub fn new(
+        goods: usize,
+        labors: usize,
+        entry: impl Fn(usize, usize) -> T,
+        productivity: impl Fn(usize) -> T,
+        threshold: T,
+    ) -> Self {
+        let mut direct = vec![Vec::new(); goods];
+        let mut needs = vec![Vec::new(); goods];
+        for g in 0..goods {
+            let producers: Vec<usize> = (0..labors).filter(|&l| entry(g, l) > T::zero()).collect();
+            let sum = producers
+                .iter()
+                .fold(T::zero(), |sum, &l| sum + entry(g, l) * productivity(l));
+            // NaN counts as not produced
+            if sum.partial_cmp(&threshold) != Some(Ordering::Greater) {
+                continue;
+            }
+            let mut amounts = vec![T::zero(); goods];
+            for &l in &producers {
+                direct[g].push((l, productivity(l) / sum));
+                for (g2, amount) in amounts.iter_mut().enumerate() {
+                    let value = entry(g2, l);
+                    if value < T::zero() {
+                        *amount -= value / sum;
+                    }
+                }
+            }
+            needs[g] = (0..goods)
+                .filter(|&g2| amounts[g2] != T::zero())
+                .map(|g2| (g2, amounts[g2]))
+                .collect();
+        }
+        RecipeGraph { direct, needs }
+    }
+
+    /// Strongly connected components by Tarjan's algorithm, each component comes after all
+    /// the components its goods need
+    pub fn components(&self) -> Vec<Vec<usize>> {
+        struct Tarjan<'a, T> {
+            graph: &'a RecipeGraph<T>,
+            index: Vec<Option<usize>>,
+            low: Vec<usize>,
+            on_stack: Vec<bool>,
+            stack: Vec<usize>,
+            next: usize,
+            components: Vec<Vec<usize>>,
+        }
+        impl<T> Tarjan<'_, T> {
+            fn visit(&mut self, g: usize) {
+                self.index[g] = Some(self.next);
+                self.low[g] = self.next;
+                self.next += 1;
+                self.stack.push(g);
+                self.on_stack[g] = true;
+                for &(g2, _) in &self.graph.needs[g] {
+                    match self.index[g2] {
+                        None => {
+                            self.visit(g2);
+                            self.low[g] = self.low[g].min(self.low[g2]);
+                        }
+                        Some(index) if self.on_stack[g2] => {
+                            self.low[g] = self.low[g].min(index);
+                        }
+                        Some(_) => {}
+                    }
+                }
+                if Some(self.low[g]) == self.index[g] {
+                    let mut component = Vec::new();
+                    loop {
+                        let v = self.stack.pop().unwrap();
+                        self.on_stack[v] = false;
+                        component.push(v);
+                        if v == g {
+                            break;
+                        }
+                    }
+                    self.components.push(component);
+                }
+            }
+        }
+        let n = self.needs.len();
+        let mut tarjan = Tarjan {
+            graph: self,
+            index: vec![None; n],
+            low: vec![0; n],
+            on_stack: vec![false; n],
+            stack: Vec::new(),
+            next: 0,
+            components: Vec::new(),
+        };
+        for g in 0..n {
+            if tarjan.index[g].is_none() {
+                tarjan.visit(g);
+            }
+        }
+        tarjan.components
+    }
+
+    fn is_cycle(&self, component: &[usize]) -> bool {
+        component.len() > 1
+            || self.needs[component[0]]
+                .iter()
+                .any(|(g2, _)| *g2 == component[0])
+    }
+
+    // the needs within a component
+    fn internal(&self, component: &[usize]) -> na::DMatrix<T> {
+        let n = component.len();
+        let mut a = na::DMatrix::<T>::zeros(n, n);
+        for (r, &g) in component.iter().enumerate() {
+            for &(g2, amount) in &self.needs[g] {
+                if let Some(c) = component.iter().position(|&other| other == g2) {
+                    a[(r, c)] += amount;
+                }
+            }
+        }
+        a
+    }
+
+    // Perron root of the nonnegative matrix `a`, by power iteration on I + a, which has the
+    // same eigenvectors but is not periodic
+    fn gain(a: &na::DMatrix<T>) -> T {
+        let n = a.nrows();
+        let shifted = a + na::DMatrix::<T>::identity(n, n);
+        let mut v = na::DVector::<T>::from_element(n, T::one());
+        let mut ratio = T::one();
+        for _ in 0..GAIN_ITERATIONS {
+            let next = &shifted * &v;
+            let norm = next.amax();
+            if norm == T::zero() {
+                return T::zero();
+            }
+            ratio = norm / v.amax();
+            v = next / norm;
+        }
+        ratio - T::one()
+    }
+
+    /// All cycles with their gain
+    pub fn cycles(&self) -> Vec<Cycle<T>> {
+        self.components()
+            .into_iter()
+            .filter(|component| self.is_cycle(component))
+            .map(|goods| Cycle {
+                gain: Self::gain(&self.internal(&goods)),
+                goods,
+            })
+            .collect()
+    }
+
+    // effort of the goods in `component` from the efforts of the goods they need outside of it,
+    // with the cycle if it is not productive
+    fn solve_component(
+        &self,
+        component: &[usize],
+        effort: &[Vec<T>],
+        labors: usize,
+    ) -> (na::DMatrix<T>, Option<Cycle<T>>) {
+        // direct labor plus the inputs from outside the component
+        let mut b = na::DMatrix::<T>::zeros(component.len(), labors);
+        for (r, &g) in component.iter().enumerate() {
+            for &(l, amount) in &self.direct[g] {
+                b[(r, l)] += amount;
+            }
+            for &(g2, amount) in &self.needs[g] {
+                if !component.contains(&g2) {
+                    for l in 0..labors {
+                        b[(r, l)] += amount * effort[g2][l];
+                    }
+                }
+            }
+        }
+        if !self.is_cycle(component) {
+            return (b, None);
+        }
+        let a = self.internal(component);
+        let gain = Self::gain(&a);
+        let n = component.len();
+        let e = if gain < T::one() - na::convert(GAIN_TOLERANCE) {
+            (na::DMatrix::<T>::identity(n, n) - a).lu().solve(&b)
+        } else {
+            None
+        };
+        match e {
+            Some(e) if e.iter().all(|v| v.is_finite()) => (e, None),
+            _ => {
+                let cycle = Cycle {
+                    goods: component.to_vec(),
+                    gain: gain.max(T::one()),
+                };
+                (na::DMatrix::zeros(n, labors), Some(cycle))
+            }
+        }
+    }
+
+    /// Labor effort of every good including all its inputs, over any depth. Goods are done
+    /// in topological order of their components, a cycle is solved as one linear system
+    /// (I - A) e = b, the limit of the geometric series of going around it.
+    pub fn propagate(&self, labors: usize) -> Propagation<T> {
+        let mut effort = vec![vec![T::zero(); labors]; self.needs.len()];
+        let mut non_productive = Vec::new();
+        for component in self.components() {
+            let (e, cycle) = self.solve_component(&component, &effort, labors);
+            for (r, &g) in component.iter().enumerate() {
+                for l in 0..labors {
+                    effort[g][l] = e[(r, l)];
+                }
+            }
+            non_productive.extend(cycle);
+        }
+        Propagation {
+            effort,
+            non_productive,
+        }
+    }
+
+    /// Components grouped by depth in the supply chain: those of a level only need goods of
+    /// earlier levels, so each level can be solved in parallel
+    pub fn levels(&self, components: &[Vec<usize>]) -> Vec<Vec<usize>> {
+        let mut component_of = vec![0; self.needs.len()];
+        for (c, component) in components.iter().enumerate() {
+            for &g in component {
+                component_of[g] = c;
+            }
+        }
+        // components are in topological order, the needed ones come first
+        let mut level = vec![0; components.len()];
+        let mut levels: Vec<Vec<usize>> = Vec::new();
+        for (c, component) in components.iter().enumerate() {
+            level[c] = component
+                .iter()
+                .flat_map(|&g| &self.needs[g])
+                .map(|&(g2, _)| component_of[g2])
+                .filter(|&c2| c2 != c)
+                .map(|c2| level[c2] + 1)
+                .max()
+                .unwrap_or(0);
+            if levels.len() <= level[c] {
+                levels.resize(level[c] + 1, Vec::new());
+            }
+            levels[level[c]].push(c);
+        }
+        levels
+    }
+
+    /// `propagate` on all threads: the components of each level are solved in parallel into
+    /// their own buffers. Every component is computed exactly as in `propagate`, so the
+    /// result is bit-identical.
+    pub fn propagate_parallel(&self, labors: usize) -> Propagation<T> {
+        let components = self.components();
+        let mut effort = vec![vec![T::zero(); labors]; self.needs.len()];
+        let mut non_productive = Vec::new();
+        for level in self.levels(&components) {
+            let solved: Vec<_> = level
+                .par_iter()
+                .map(|&c| (c, self.solve_component(&components[c], &effort, labors)))
+                .collect();
+            for (c, (e, cycle)) in solved {
+                for (r, &g) in components[c].iter().enumerate() {
+                    for l in 0..labors {
+                        effort[g][l] = e[(r, l)];
+                    }
+                }
+                if let Some(cycle) = cycle {
+                    non_productive.push((c, cycle));
+                }
+            }
+        }
+        // in the order `propagate` finds them
+        non_productive.sort_by_key(|(c, _)| *c);
+        Propagation {
+            effort,
+            non_productive: non_productive.into_iter().map(|(_, cycle)| cycle).collect(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Propagation, RecipeGraph};
+
+    #[test]
+    fn tools_make_tools() {
+        // labor 0 makes 2 tools from 1 tool and 1 ore, labor 1 makes 1 ore
+        let x = [[1.0, 0.0], [-1.0, 1.0]];
+        let graph = RecipeGraph::new(2, 2, |r, c| x[r][c], |_| 1.0_f64, 0.0);
+        let components = graph.components();
+        assert_eq!(components, vec![vec![1], vec![0]]);
+        let cycles = graph.cycles();
+        assert_eq!(cycles.len(), 0);
+
+        let x = [[2.0, 0.0, -1.0], [-1.0, 1.0, 0.0], [-1.0, 0.0, 1.0]];
+        // tools (0) need ore (1) and machines (2), machines need tools
+        let graph = RecipeGraph::new(3, 3, |r, c| x[r][c], |_| 1.0_f64, 0.0);
+        let cycles = graph.cycles();
+        assert_eq!(cycles.len(), 1);
+        assert_eq!(cycles[0].goods.len(), 2);
+        // tools need 1/2 machine, machines need 1 tool
+        assert!((cycles[0].gain - 0.5_f64.sqrt()).abs() <= 1e-9);
+        let propagation = graph.propagate(3);
+        assert!(propagation.non_productive.is_empty());
+        assert_eq!(graph.propagate_parallel(3).effort, propagation.effort);
+        assert_eq!(graph.levels(&graph.components()).len(), 2);
+        // e_tool = (1 + e_ore + e_machine) / 2, e_machine = 1 + e_tool, e_ore = 1
+        // so e_tool = 3, e_machine = 4, split over the labors
+        let total = |g: usize| propagation.effort[g].iter().sum::<f64>();
+        assert!((total(0) - 3.0).abs() <= 1e-9);
+        assert!((total(1) - 1.0).abs() <= 1e-9);
+        assert!((total(2) - 4.0).abs() <= 1e-9);
+
+        // machines need three tools: the loop eats more than it makes
+        let x = [[2.0, 0.0, -3.0], [-1.0, 1.0, 0.0], [-1.0, 0.0, 1.0]];
+        let graph = RecipeGraph::new(3, 3, |r, c| x[r][c], |_| 1.0_f64, 0.0);
+        let propagation = graph.propagate(3);
+        assert_eq!(propagation.non_productive.len(), 1);
+        assert!(propagation.non_productive[0].gain >= 1.0);
+        assert_eq!(propagation.effort[1], vec![0.0, 1.0, 0.0]);
+        assert_eq!(propagation.effort[0], vec![0.0; 3]);
+    }
+
+    #[test]
+    fn singular_cycle() {
+        // machines need two tools and tools half a machine: the loop makes exactly what it eats
+        let x = [[2.0, 0.0, -2.0], [-1.0, 1.0, 0.0], [-1.0, 0.0, 1.0]];
+        let graph = RecipeGraph::new(3, 3, |r, c| x[r][c], |_| 1.0_f64, 0.0);
+        let propagation = graph.propagate(3);
+        assert_eq!(propagation.non_productive.len(), 1);
+        assert_eq!(propagation.non_productive[0].gain, 1.0);
+        assert_eq!(propagation.effort[0], vec![0.0; 3]);
+        assert_eq!(propagation.effort[1], vec![0.0, 1.0, 0.0]);
+
+        // a loop of three whose gain is 1 but is estimated just below it
+        let (p, q) = (2.0 / 7.0, 54.0 / 11.0);
+        let x = [[1.0, 0.0, -1.0 / (p * q)], [-p, 1.0, 0.0], [0.0, -q, 1.0]];
+        let graph = RecipeGraph::new(3, 3, |r, c| x[r][c], |_| 1.0_f64, 0.0);
+        assert!(graph.cycles()[0].gain < 1.0);
+        let propagation = graph.propagate(3);
+        assert_eq!(propagation.non_productive.len(), 1);
+        assert!(propagation.non_productive[0].gain >= 1.0);
+        assert_eq!(propagation.effort, vec![vec![0.0; 3]; 3]);
+    }
+
+    #[test]
+    fn parallel() {
+        // ore (0) -> tools (1) <-> machines (2) -> cars (3), and a loop of seeds (4) eating
+        // more than it makes feeding farms (5), which cars need too
+        let x = [
+            [1.0, -1.0, 0.0, 0.0, 0.0, 0.0],
+            [0.0, 2.0, -1.0, -1.0, 0.0, 0.0],
+            [0.0, -1.0, 1.0, -1.0, 0.0, 0.0],
+            [0.0, 0.0, 0.0, 1.0, 0.0, 0.0],
+            [0.0, 0.0, 0.0, 0.0, 1.0, -1.0],
+            [0.0, 0.0, 0.0, -1.0, -2.0, 1.0],
+        ];
+        let graph = RecipeGraph::new(6, 6, |r, c| x[r][c], |_| 1.0_f64, 0.0);
+        assert!(graph.levels(&graph.components()).len() > 2);
+        let serial = graph.propagate(6);
+        let parallel = graph.propagate_parallel(6);
+        assert_eq!(parallel.effort, serial.effort);
+        let goods = |propagation: &Propagation<f64>| {
+            let cycles = &propagation.non_productive;
+            cycles
+                .iter()
+                .map(|cycle| cycle.goods.clone())
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(goods(&parallel), goods(&serial));
+        assert_eq!(goods(&serial).len(), 1);
+        assert!(serial.effort[3].iter().all(|v| v.is_finite()));
+    }
+}
@@ -1,6 +1,8 @@
 // from https://github.com/dirkschumacher/rs-leastsquare
 extern crate nalgebra as na;
 
+/// QR least squares. Each column of `y` is a separate right-hand side, `x` is factorised once
+/// for all of them.
 pub fn least_squares<T>(
     x: &na::DMatrix<T>,
     y: &na::DMatrix<T>,
@@ -14,13 +16,328 @@ where
 {
     let qr_result = x.clone().qr();
     let qty = qr_result.q().transpose() * y;
-    let beta_hat = qr_result.r().solve_upper_triangular(&qty);
-    beta_hat
+    qr_result.r().solve_upper_triangular(&qty)
+}
+
+/// `least_squares` for many small independent problems with fixed size, e.g. one per site.
+/// Problems sharing the same `x` are factorised only once. Like `least_squares` this needs
+/// full column rank, `None` where `x` is singular.
+pub fn least_squares_batch<T, const M: usize, const N: usize>(
+    problems: &[(na::SMatrix<T, M, N>, na::SMatrix<T, M, 1>)],
+) -> Vec<Option<na::SMatrix<T, N, 1>>>
+where
+    T: na::RealField + Copy,
+{
+    // Qᵀ and R of every distinct x
+    let mut factors: Vec<(na::SMatrix<T, M, N>, na::DMatrix<T>, na::DMatrix<T>)> = Vec::new();
+    problems
+        .iter()
+        .map(|(x, y)| {
+            let index = match factors.iter().position(|(known, _, _)| known == x) {
+                Some(index) => index,
+                None => {
+                    let qr = na::DMatrix::from_fn(M, N, |r, c| x[(r, c)]).qr();
+                    factors.push((*x, qr.q().transpose(), qr.r()));
+                    factors.len() - 1
+                }
+            };
+            let (_, qt, r) = &factors[index];
+            let qty = qt * na::DMatrix::from_fn(M, 1, |r, _| y[r]);
+            r.solve_upper_triangular(&qty)
+                .map(|beta| na::SMatrix::<T, N, 1>::from_fn(|r, _| beta[r]))
+        })
+        .collect()
+}
+
+/// Least squares solution together with the detected numerical rank of `x`
+#[derive(Debug)]
+pub struct RankRevealing<T> {
+    pub beta: na::DMatrix<T>,
+    pub rank: usize,
+}
+
+/// Householder QR with column pivoting by largest remaining column norm: x P = Q R.
+/// Stops once the remaining columns are below `tolerance` (relative to the first pivot).
+/// Returns R (upper trapezoidal), Qᵀy, the column order and the rank.
+fn pivoted_qr<T>(
+    x: &na::DMatrix<T>,
+    y: &na::DMatrix<T>,
+    tolerance: T,
+) -> (na::DMatrix<T>, na::DMatrix<T>, Vec<usize>, usize)
+where
+    T: na::RealField + Copy,
+{
+    let (m, n) = x.shape();
+    let mut r = x.clone();
+    let mut qty = y.clone();
+    let mut permutation: Vec<usize> = (0..n).collect();
+    let mut threshold = T::zero();
+    let mut rank = 0;
+    for k in 0..m.min(n) {
+        let (pivot, norm) = (k..n)
+            .map(|j| (j, r.view_range(k.., j..j + 1).norm()))
+            .fold(
+                (k, T::zero()),
+                |best, col| if col.1 > best.1 { col } else { best },
+            );
+        if k == 0 {
+            threshold = norm * tolerance;
+        }
+        if norm <= threshold || norm == T::zero() {
+            break; // remaining columns are numerically dependent
+        }
+        r.swap_columns(k, pivot);
+        permutation.swap(k, pivot);
+        let mut v = r.view_range(k.., k..k + 1).clone_owned();
+        let alpha = if v[0] > T::zero() { -norm } else { norm };
+        v[0] -= alpha;
+        let scale = (T::one() + T::one()) / v.norm_squared();
+        for target in [&mut r, &mut qty] {
+            let mut rows = target.rows_mut(k, m - k);
+            let projection = v.transpose() * &rows;
+            rows -= &v * projection * scale;
+        }
+        rank += 1;
+    }
+    (r, qty, permutation, rank)
+}
+
+/// Column pivoted QR least squares, works for rank deficient `x`.
+/// This is the basic solution: variables of dependent columns are left at zero,
+/// use `cod_least_squares` for the minimum norm solution.
+pub fn pivoted_qr_least_squares<T>(
+    x: &na::DMatrix<T>,
+    y: &na::DMatrix<T>,
+    tolerance: T,
+) -> RankRevealing<T>
+where
+    T: na::RealField + Copy,
+{
+    let (r, qty, permutation, rank) = pivoted_qr(x, y, tolerance);
+    let z = r
+        .view((0, 0), (rank, rank))
+        .solve_upper_triangular(&qty.rows(0, rank))
+        .unwrap();
+    let mut beta = na::DMatrix::<T>::zeros(x.ncols(), y.ncols());
+    for (i, &col) in permutation.iter().take(rank).enumerate() {
+        beta.row_mut(col).copy_from(&z.row(i));
+    }
+    RankRevealing { beta, rank }
+}
+
+/// Minimum norm least squares by complete orthogonal decomposition:
+/// after pivoted QR the leading rows [R11 R12] are reduced once more by a QR of their
+/// transpose, which removes the dependent columns without leaving them at zero.
+pub fn cod_least_squares<T>(
+    x: &na::DMatrix<T>,
+    y: &na::DMatrix<T>,
+    tolerance: T,
+) -> RankRevealing<T>
+where
+    T: na::RealField + Copy,
+{
+    let (r, qty, permutation, rank) = pivoted_qr(x, y, tolerance);
+    let mut beta = na::DMatrix::<T>::zeros(x.ncols(), y.ncols());
+    if rank == 0 {
+        return RankRevealing { beta, rank };
+    }
+    // [R11 R12] = Lᵀ Zᵀ
+    let qr = r.rows(0, rank).transpose().qr();
+    let u = qr
+        .r()
+        .transpose()
+        .solve_lower_triangular(&qty.rows(0, rank))
+        .unwrap();
+    let w = qr.q() * u;
+    for (i, &col) in permutation.iter().enumerate() {
+        beta.row_mut(col).copy_from(&w.row(i));
+    }
+    RankRevealing { beta, rank }
+}
+
+/// Factorisation chosen by `solve_auto`
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Method {
+    /// square with full rank
+    Lu,
+    /// overdetermined, full column rank and well conditioned
+    NormalCholesky,
+    /// overdetermined, full column rank but too ill-conditioned to square the condition
+    Qr,
+    /// underdetermined or rank deficient, minimum norm solution
+    Svd,
+}
+
+/// Least squares solution together with the method used and the detected rank
+#[derive(Debug)]
+pub struct AutoSolution<T> {
+    pub beta: na::DMatrix<T>,
+    pub method: Method,
+    pub rank: usize,
+}
+
+/// Least squares for any shape of `x`: the shape and the rank found by a column pivoted QR
+/// decide between LU, Cholesky on the normal equations, QR and SVD.
+/// The normal equations square the condition number, so they are only used while
+/// κ² ε stays below √ε, estimated from the diagonal of the pivoted R.
+/// `tolerance` is relative to the largest pivot as in `pivoted_qr_least_squares`.
+pub fn solve_auto<T>(x: &na::DMatrix<T>, y: &na::DMatrix<T>, tolerance: T) -> AutoSolution<T>
+where
+    T: na::RealField + Copy,
+{
+    let (m, n) = x.shape();
+    let (r, _, _, rank) = pivoted_qr(x, &na::DMatrix::zeros(m, 0), tolerance);
+    let full_rank = rank == n && n > 0;
+    let method = if !full_rank || m < n {
+        Method::Svd
+    } else if m == n {
+        Method::Lu
+    } else {
+        let condition = r[(0, 0)].abs() / r[(n - 1, n - 1)].abs();
+        let eps = T::default_epsilon();
+        if condition * condition * eps <= eps.sqrt() {
+            Method::NormalCholesky
+        } else {
+            Method::Qr
+        }
+    };
+    let beta = match method {
+        Method::Lu => x.clone().lu().solve(y),
+        Method::NormalCholesky => x
+            .tr_mul(x)
+            .cholesky()
+            .map(|cholesky| cholesky.solve(&x.tr_mul(y))),
+        Method::Qr => least_squares(x, y),
+        Method::Svd => {
+            let svd = padded_svd(x);
+            let eps = svd.singular_values.max() * tolerance;
+            let mut padded = na::DMatrix::<T>::zeros(svd.u.as_ref().unwrap().nrows(), y.ncols());
+            padded.rows_mut(0, m).copy_from(y);
+            svd.solve(&padded, eps).ok()
+        }
+    };
+    // rank was checked, a failed factorisation can only come from round-off at the tolerance
+    let beta = beta.unwrap_or_else(|| cod_least_squares(x, y, tolerance).beta);
+    AutoSolution { beta, method, rank }
+}
+
+/// Which singular values the pseudo inverse keeps, the others are treated as zero
+#[derive(Clone, Copy, Debug)]
+pub enum Truncation<T> {
+    /// keep every non zero singular value (what econ.py does)
+    Exact,
+    /// drop singular values below this value
+    Absolute(T),
+    /// drop singular values below this fraction of the largest one (numpy's rcond)
+    Relative(T),
+    /// keep only the largest singular values
+    Rank(usize),
+}
+
+/// Moore-Penrose pseudo inverse V Σ⁺ Uᵀ from the SVD of `x`
+pub fn pseudo_inverse<T>(x: &na::DMatrix<T>, truncation: Truncation<T>) -> na::DMatrix<T>
+where
+    T: na::RealField + Copy,
+{
+    let svd = x.clone().svd(true, true);
+    let sigma = &svd.singular_values;
+    let keep = |i: usize| match truncation {
+        Truncation::Exact => sigma[i] > T::zero(),
+        Truncation::Absolute(eps) => sigma[i] > eps,
+        Truncation::Relative(rcond) => sigma[i] > rcond * sigma.max(),
+        Truncation::Rank(rank) => i < rank && sigma[i] > T::zero(),
+    };
+    let inverse = na::DVector::<T>::from_fn(sigma.len(), |i, _| {
+        if keep(i) {
+            T::one() / sigma[i]
+        } else {
+            T::zero()
+        }
+    });
+    let v = svd.v_t.unwrap().transpose();
+    let u_t = svd.u.unwrap().transpose();
+    v * na::DMatrix::from_diagonal(&inverse) * u_t
+}
+
+/// SVD of `x` padded with zero rows, so that `v_t` always spans the full column space
+/// (nalgebra only computes the thin SVD, which would lose the null space of wide matrices)
+fn padded_svd<T>(x: &na::DMatrix<T>) -> na::SVD<T, na::Dyn, na::Dyn>
+where
+    T: na::RealField + Copy,
+{
+    let rows = x.nrows().max(x.ncols());
+    let mut padded = na::DMatrix::<T>::zeros(rows, x.ncols());
+    padded.rows_mut(0, x.nrows()).copy_from(x);
+    padded.svd(true, true)
+}
+
+/// Orthonormal basis (as columns) of the directions `x` maps to (numerically) zero,
+/// `tolerance` is relative to the largest singular value
+pub fn null_space<T>(x: &na::DMatrix<T>, tolerance: T) -> na::DMatrix<T>
+where
+    T: na::RealField + Copy,
+{
+    let svd = padded_svd(x);
+    let eps = svd.singular_values.max() * tolerance;
+    null_space_of(&svd, eps)
+}
+
+fn null_space_of<T>(svd: &na::SVD<T, na::Dyn, na::Dyn>, eps: T) -> na::DMatrix<T>
+where
+    T: na::RealField + Copy,
+{
+    let v_t = svd.v_t.as_ref().unwrap();
+    let free: Vec<usize> = (0..svd.singular_values.len())
+        .filter(|&i| svd.singular_values[i] <= eps)
+        .collect();
+    na::DMatrix::<T>::from_fn(v_t.ncols(), free.len(), |r, c| v_t[(free[c], r)])
+}
+
+/// Prioritised (lexicographic) least squares.
+///
+/// Each level is a pair of equation matrix and target, the first level has the highest
+/// priority. Levels are solved in order, every later level only moves the solution within
+/// the null space of the earlier ones, so their optimum is kept exactly.
+/// Starting from `x0` the smallest change which achieves this is returned.
+/// `tolerance` is relative to the largest singular value of each level.
+pub fn lexicographic_least_squares<T>(
+    levels: &[(na::DMatrix<T>, na::DMatrix<T>)],
+    x0: &na::DMatrix<T>,
+    tolerance: T,
+) -> na::DMatrix<T>
+where
+    T: na::RealField + Copy,
+{
+    let n = x0.nrows();
+    let mut x = x0.clone();
+    // basis of the directions the previous levels leave undetermined
+    let mut null_space = na::DMatrix::<T>::identity(n, n);
+    for (equation, target) in levels {
+        if null_space.ncols() == 0 {
+            break; // everything is fixed by higher priorities
+        }
+        let projected = equation * &null_space;
+        let residual = target - equation * &x;
+        let svd = padded_svd(&projected);
+        let eps = svd.singular_values.max() * tolerance;
+        let mut padded_residual = na::DMatrix::<T>::zeros(svd.u.as_ref().unwrap().nrows(), 1);
+        padded_residual
+            .rows_mut(0, residual.nrows())
+            .copy_from(&residual);
+        let z = svd.solve(&padded_residual, eps).unwrap();
+        x += &null_space * z;
+        null_space = &null_space * null_space_of(&svd, eps);
+    }
+    x
 }
 
 #[cfg(test)]
 mod tests {
-    use super::least_squares;
+    use super::{
+        cod_least_squares, least_squares, least_squares_batch, lexicographic_least_squares,
+        pivoted_qr_least_squares, pseudo_inverse, solve_auto, Method, Truncation,
+    };
+    use crate::matrix_io::parse_csv;
     extern crate nalgebra as na;
 
     #[test]
@@ -41,10 +358,131 @@ mod tests {
         let x = na::DMatrix::from_row_slice(2, 2, &[1.0, 1.0, 2.0, 2.0]);
         let y = na::DMatrix::from_column_slice(2, 1, &[3.0, 5.0]);
 
-        let res = least_squares(&x, &y);
-        match res {
-            None => assert!(true),
-            Some(_x) => panic!("Should not happen"),
+        assert!(least_squares(&x, &y).is_none(), "Should not happen");
+    }
+
+    #[test]
+    fn lexicographic() {
+        // first priority: x0 + x1 = 2, second priority: x0 = 5 and x1 = 0
+        let first = (
+            na::DMatrix::from_row_slice(1, 2, &[1.0, 1.0]),
+            na::DMatrix::from_column_slice(1, 1, &[2.0]),
+        );
+        let second = (
+            na::DMatrix::from_row_slice(2, 2, &[1.0, 0.0, 0.0, 1.0]),
+            na::DMatrix::from_column_slice(2, 1, &[5.0, 0.0]),
+        );
+        let x0 = na::DMatrix::zeros(2, 1);
+        let res = lexicographic_least_squares(&[first, second], &x0, 1e-10);
+        let eps = 1e-10;
+        assert!((res[0] + res[1] - 2.0_f64).abs() <= eps);
+        assert!((res[0] - 3.5).abs() <= eps);
+        assert!((res[1] - -1.5).abs() <= eps);
+    }
+
+    #[test]
+    fn rank_deficient() {
+        let x = na::DMatrix::from_row_slice(2, 2, &[1.0, 1.0, 2.0, 2.0]);
+        let y = na::DMatrix::from_column_slice(2, 1, &[3.0, 5.0]);
+        let eps = 1e-10;
+
+        let basic = pivoted_qr_least_squares(&x, &y, 1e-10);
+        assert_eq!(basic.rank, 1);
+        assert!((basic.beta[0] + basic.beta[1] - 2.6_f64).abs() <= eps);
+        assert!(basic.beta[0] == 0.0 || basic.beta[1] == 0.0);
+
+        let res = cod_least_squares(&x, &y, 1e-10);
+        assert_eq!(res.rank, 1);
+        assert!((res.beta[0] - 1.3_f64).abs() <= eps);
+        assert!((res.beta[1] - 1.3_f64).abs() <= eps);
+    }
+
+    #[test]
+    fn full_rank_matches_qr() {
+        let x = na::DMatrix::from_row_slice(3, 2, &[1.0, 3.0, 2.0, 13.0, 0.5, -1.0]);
+        let y = na::DMatrix::from_column_slice(3, 1, &[3.0, 5.0, 1.0]);
+        let expected = least_squares(&x, &y).unwrap();
+        let res = cod_least_squares(&x, &y, 1e-10);
+        assert_eq!(res.rank, 2);
+        assert!((res.beta - expected).amax() <= 1e-10_f64);
+    }
+
+    #[test]
+    fn many_right_hand_sides() {
+        let x = na::DMatrix::from_row_slice(3, 2, &[1.0, 3.0, 2.0, 13.0, 0.5, -1.0]);
+        let y = na::DMatrix::from_row_slice(3, 2, &[3.0, 1.0, 5.0, 0.0, 1.0, -2.0]);
+        let all = least_squares(&x, &y).unwrap();
+        let eps = 1e-10;
+        for k in 0..2 {
+            let single = least_squares(&x, &y.columns(k, 1).into_owned()).unwrap();
+            assert!((all.column(k) - single).amax() <= eps);
         }
+
+        let sx = na::SMatrix::<f64, 3, 2>::from_fn(|r, c| x[(r, c)]);
+        let other = na::SMatrix::<f64, 3, 2>::from_row_slice(&[1.0, 1.0, 2.0, 2.0, 3.0, 3.0]);
+        let problems = [
+            (sx, na::SMatrix::<f64, 3, 1>::from_fn(|r, _| y[(r, 0)])),
+            (other, na::SMatrix::<f64, 3, 1>::from_fn(|r, _| y[(r, 0)])),
+            (sx, na::SMatrix::<f64, 3, 1>::from_fn(|r, _| y[(r, 1)])),
+        ];
+        let batch = least_squares_batch(&problems);
+        assert!((batch[0].unwrap() - all.column(0)).amax() <= eps);
+        let dynamic_other = na::DMatrix::from_fn(3, 2, |r, c| other[(r, c)]);
+        // rank deficient, but the same factorisation gives the same values
+        let expected = least_squares(&dynamic_other, &y.columns(0, 1).into_owned()).unwrap();
+        assert_eq!(batch[1].unwrap().as_slice(), expected.as_slice());
+        assert!((batch[2].unwrap() - all.column(1)).amax() <= eps);
+    }
+
+    #[test]
+    fn auto_method() {
+        let y3 = na::DMatrix::from_column_slice(3, 1, &[3.0, 5.0, 1.0]);
+        let eps = 1e-10;
+
+        let square = na::DMatrix::from_row_slice(2, 2, &[1.0, 3.0, 2.0, 13.0]);
+        let y2 = na::DMatrix::from_column_slice(2, 1, &[3.0, 5.0]);
+        let res = solve_auto(&square, &y2, 1e-10);
+        assert_eq!(res.method, Method::Lu);
+        assert!((res.beta - least_squares(&square, &y2).unwrap()).amax() <= eps);
+
+        let tall = na::DMatrix::from_row_slice(3, 2, &[1.0, 3.0, 2.0, 13.0, 0.5, -1.0]);
+        let res = solve_auto(&tall, &y3, 1e-10);
+        assert_eq!(res.method, Method::NormalCholesky);
+        assert!((res.beta - least_squares(&tall, &y3).unwrap()).amax() <= eps);
+
+        let nearly_dependent =
+            na::DMatrix::from_row_slice(3, 2, &[1.0, 1.0, 1.0, 1.0 + 1e-6, 1.0, 1.0]);
+        let res = solve_auto(&nearly_dependent, &y3, 1e-10);
+        assert_eq!(res.method, Method::Qr);
+        let expected = least_squares(&nearly_dependent, &y3).unwrap();
+        assert!((res.beta - expected).amax() <= 1e-6);
+
+        let deficient = na::DMatrix::from_row_slice(2, 2, &[1.0, 1.0, 2.0, 2.0]);
+        let res = solve_auto(&deficient, &y2, 1e-10);
+        assert_eq!((res.method, res.rank), (Method::Svd, 1));
+        assert!((res.beta[0] - 1.3_f64).abs() <= eps);
+        assert!((res.beta[1] - 1.3_f64).abs() <= eps);
+
+        let wide = tall.transpose();
+        let res = solve_auto(&wide, &y2, 1e-10);
+        assert_eq!((res.method, res.rank), (Method::Svd, 2));
+        assert!((&wide * &res.beta - &y2).amax() <= eps);
+    }
+
+    #[test]
+    fn econ_py_pseudo_inverse() {
+        // the recipe matrix of econ.py
+        let (x, _) = parse_csv(include_str!("../fixtures/econ.csv")).unwrap();
+        assert_eq!(x.shape(), (46, 35));
+        let pinv = pseudo_inverse(&x, Truncation::Relative(1e-12));
+        // econ.py's check: pseudoinv @ X
+        let test = &pinv * &x;
+        let eps = 1e-10;
+        // X has full column rank, so this is the identity
+        assert!((test - na::DMatrix::identity(35, 35)).amax() <= eps);
+        assert!((&pinv * &x * &pinv - &pinv).amax() <= eps);
+        // same as nalgebra's own pseudo inverse
+        let reference = x.clone().pseudo_inverse(1e-9).unwrap();
+        assert!((pinv - reference).amax() <= eps);
     }
 }
@@ -0,0 +1,387 @@
+// Sparse matrices and iterative least squares, the cost per iteration is proportional to the
+// number of recipe entries instead of goods × labors
+
+/// Operations the iterative solvers need from a matrix
+pub trait LinearOperator<T> {
+    fn nrows(&self) -> usize;
+    fn ncols(&self) -> usize;
+    /// A x
+    fn mul_vec(&self, x: &na::DVector<T>) -> na::DVector<T>;
+    /// Aᵀ y
+    fn tr_mul_vec(&self, y: &na::DVector<T>) -> na::DVector<T>;
+}
+
+/// Compressed sparse row matrix
+#[derive(Clone, Debug)]
+pub struct CsrMatrix<T> {
+    nrows: usize,
+    ncols: usize,
+    // entries of row r are at row_offsets[r]..row_offsets[r + 1]
+    row_offsets: Vec<usize>,
+    col_indices: Vec<usize>,
+    values: Vec<T>,
+}
+
+/// Compressed sparse column matrix, stored as the CSR form of its transpose
+#[derive(Clone, Debug)]
+pub struct CscMatrix<T> {
+    transposed: CsrMatrix<T>,
+}
+
+impl<T: na::RealField + Copy> CsrMatrix<T> {
+    /// Keeps the entries of `f` which are not zero
+    pub fn from_fn<F: Fn(usize, usize) -> T>(nrows: usize, ncols: usize, f: F) -> Self {
+        let mut row_offsets = Vec::with_capacity(nrows + 1);
+        let mut col_indices = Vec::new();
+        let mut values = Vec::new();
+        row_offsets.push(0);
+        for r in 0..nrows {
+            for c in 0..ncols {
+                let value = f(r, c);
+                if value != T::zero() {
+                    col_indices.push(c);
+                    values.push(value);
+                }
+            }
+            row_offsets.push(values.len());
+        }
+        CsrMatrix {
+            nrows,
+            ncols,
+            row_offsets,
+            col_indices,
+            values,
+        }
+    }
+
+    pub fn from_dense(x: &na::DMatrix<T>) -> Self {
+        Self::from_fn(x.nrows(), x.ncols(), |r, c| x[(r, c)])
+    }
+
+    /// Builds the matrix from (row, column, value) entries, duplicates are summed
+    pub fn from_triplets(nrows: usize, ncols: usize, triplets: &[(usize, usize, T)]) -> Self {
+        let mut sorted = triplets.to_vec();
+        sorted.sort_by_key(|&(r, c, _)| (r, c));
+        let mut row_offsets = vec![0; nrows + 1];
+        let mut col_indices: Vec<usize> = Vec::with_capacity(sorted.len());
+        let mut values: Vec<T> = Vec::with_capacity(sorted.len());
+        let mut last = None;
+        for (r, c, value) in sorted {
+            assert!(r < nrows && c < ncols, "entry outside of the matrix");
+            if last == Some((r, c)) {
+                *values.last_mut().unwrap() += value;
+            } else {
+                col_indices.push(c);
+                values.push(value);
+                row_offsets[r + 1] += 1;
+                last = Some((r, c));
+            }
+        }
+        for r in 0..nrows {
+            row_offsets[r + 1] += row_offsets[r];
+        }
+        CsrMatrix {
+            nrows,
+            ncols,
+            row_offsets,
+            col_indices,
+            values,
+        }
+    }
+
+    /// Number of stored entries
+    pub fn nnz(&self) -> usize {
+        self.values.len()
+    }
+
+    /// (column, value) pairs of one row
+    pub fn row(&self, r: usize) -> impl Iterator<Item = (usize, T)> + '_ {
+        let range = self.row_offsets[r]..self.row_offsets[r + 1];
+        self.col_indices[range.clone()]
+            .iter()
+            .copied()
+            .zip(self.values[range].iter().copied())
+    }
+
+    pub fn transpose(&self) -> Self {
+        let mut triplets = Vec::with_capacity(self.nnz());
+        for r in 0..self.nrows {
+            triplets.extend(self.row(r).map(|(c, value)| (c, r, value)));
+        }
+        Self::from_triplets(self.ncols, self.nrows, &triplets)
+    }
+
+    pub fn to_csc(&self) -> CscMatrix<T> {
+        CscMatrix {
+            transposed: self.transpose(),
+        }
+    }
+
+    pub fn to_dense(&self) -> na::DMatrix<T> {
+        let mut res = na::DMatrix::zeros(self.nrows, self.ncols);
+        for r in 0..self.nrows {
+            for (c, value) in self.row(r) {
+                res[(r, c)] = value;
+            }
+        }
+        res
+    }
+}
+
+impl<T: na::RealField + Copy> LinearOperator<T> for CsrMatrix<T> {
+    fn nrows(&self) -> usize {
+        self.nrows
+    }
+
+    fn ncols(&self) -> usize {
+        self.ncols
+    }
+
+    fn mul_vec(&self, x: &na::DVector<T>) -> na::DVector<T> {
+        assert_eq!(x.len(), self.ncols);
+        na::DVector::from_fn(self.nrows, |r, _| {
+            self.row(r)
+                .fold(T::zero(), |sum, (c, value)| sum + value * x[c])
+        })
+    }
+
+    fn tr_mul_vec(&self, y: &na::DVector<T>) -> na::DVector<T> {
+        assert_eq!(y.len(), self.nrows);
+        let mut res = na::DVector::zeros(self.ncols);
+        for r in 0..self.nrows {
+            for (c, value) in self.row(r) {
+                res[c] += value * y[r];
+            }
+        }
+        res
+    }
+}
+
+impl<T: na::RealField + Copy> CscMatrix<T> {
+    pub fn from_dense(x: &na::DMatrix<T>) -> Self {
+        CscMatrix {
+            transposed: CsrMatrix::from_fn(x.ncols(), x.nrows(), |r, c| x[(c, r)]),
+        }
+    }
+
+    pub fn nnz(&self) -> usize {
+        self.transposed.nnz()
+    }
+
+    /// (row, value) pairs of one column
+    pub fn column(&self, c: usize) -> impl Iterator<Item = (usize, T)> + '_ {
+        self.transposed.row(c)
+    }
+
+    pub fn to_csr(&self) -> CsrMatrix<T> {
+        self.transposed.transpose()
+    }
+
+    pub fn to_dense(&self) -> na::DMatrix<T> {
+        self.transposed.to_dense().transpose()
+    }
+}
+
+impl<T: na::RealField + Copy> LinearOperator<T> for CscMatrix<T> {
+    fn nrows(&self) -> usize {
+        self.transposed.ncols()
+    }
+
+    fn ncols(&self) -> usize {
+        self.transposed.nrows()
+    }
+
+    fn mul_vec(&self, x: &na::DVector<T>) -> na::DVector<T> {
+        self.transposed.tr_mul_vec(x)
+    }
+
+    fn tr_mul_vec(&self, y: &na::DVector<T>) -> na::DVector<T> {
+        self.transposed.mul_vec(y)
+    }
+}
+
+/// Result of an iterative least squares solve
+#[derive(Debug)]
+pub struct IterativeSolution<T> {
+    pub x: na::DVector<T>,
+    pub iterations: usize,
+    /// ‖b - A x‖
+    pub residual_norm: T,
+    /// false if `max_iterations` was reached before the tolerance
+    pub converged: bool,
+}
+
+/// Conjugate gradients on the normal equations AᵀA x = Aᵀb without forming AᵀA.
+/// Stops when ‖Aᵀr‖ dropped below `tolerance` times its initial value.
+/// Starting from zero it converges to the minimum norm solution.
+pub fn cgls<T: na::RealField + Copy>(
+    a: &impl LinearOperator<T>,
+    b: &na::DVector<T>,
+    tolerance: T,
+    max_iterations: usize,
+) -> IterativeSolution<T> {
+    let mut x = na::DVector::zeros(a.ncols());
+    let mut r = b.clone();
+    let mut s = a.tr_mul_vec(&r);
+    let mut p = s.clone();
+    let mut gamma = s.norm_squared();
+    let limit = tolerance * gamma.sqrt();
+    let mut iterations = 0;
+    let mut converged = gamma.sqrt() <= limit;
+    while !converged && iterations < max_iterations {
+        let q = a.mul_vec(&p);
+        let delta = q.norm_squared();
+        if delta == T::zero() {
+            break;
+        }
+        let alpha = gamma / delta;
+        x.axpy(alpha, &p, T::one());
+        r.axpy(-alpha, &q, T::one());
+        s = a.tr_mul_vec(&r);
+        let gamma_new = s.norm_squared();
+        iterations += 1;
+        converged = gamma_new.sqrt() <= limit;
+        p = &s + &p * (gamma_new / gamma);
+        gamma = gamma_new;
+    }
+    IterativeSolution {
+        residual_norm: r.norm(),
+        x,
+        iterations,
+        converged,
+    }
+}
+
+/// LSQR (Paige & Saunders 1982), numerically more robust than CGLS for ill-conditioned A.
+/// Stops when ‖Aᵀr‖ ≤ `tolerance` ‖A‖ ‖r‖ (or the residual vanishes).
+pub fn lsqr<T: na::RealField + Copy>(
+    a: &impl LinearOperator<T>,
+    b: &na::DVector<T>,
+    tolerance: T,
+    max_iterations: usize,
+) -> IterativeSolution<T> {
+    let mut x = na::DVector::zeros(a.ncols());
+    let mut beta = b.norm();
+    let mut u = b.clone();
+    if beta > T::zero() {
+        u /= beta;
+    }
+    let mut v = a.tr_mul_vec(&u);
+    let mut alpha = v.norm();
+    if alpha > T::zero() {
+        v /= alpha;
+    }
+    let mut w = v.clone();
+    let mut phibar = beta;
+    let mut rhobar = alpha;
+    let mut anorm_squared = T::zero();
+    let mut iterations = 0;
+    let mut converged = beta == T::zero() || alpha == T::zero();
+    while !converged && iterations < max_iterations {
+        // bidiagonalization
+        u = a.mul_vec(&v) - &u * alpha;
+        beta = u.norm();
+        if beta > T::zero() {
+            u /= beta;
+        }
+        anorm_squared += alpha * alpha + beta * beta;
+        v = a.tr_mul_vec(&u) - &v * beta;
+        alpha = v.norm();
+        if alpha > T::zero() {
+            v /= alpha;
+        }
+        // plane rotation to eliminate beta
+        let rho = (rhobar * rhobar + beta * beta).sqrt();
+        let c = rhobar / rho;
+        let s = beta / rho;
+        let theta = s * alpha;
+        rhobar = -c * alpha;
+        let phi = c * phibar;
+        phibar *= s;
+        x.axpy(phi / rho, &w, T::one());
+        w = &v - &w * (theta / rho);
+        iterations += 1;
+        // phibar is ‖r‖, phibar α |c| is ‖Aᵀr‖
+        let normal_residual = phibar * alpha * c.abs();
+        converged =
+            phibar == T::zero() || normal_residual <= tolerance * anorm_squared.sqrt() * phibar;
+    }
+    IterativeSolution {
+        x,
+        iterations,
+        residual_norm: phibar,
+        converged,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{cgls, lsqr, CscMatrix, CsrMatrix, LinearOperator};
+    use crate::rs_leastsquare::cod_least_squares;
+
+    fn example() -> na::DMatrix<f64> {
+        na::DMatrix::from_row_slice(
+            4,
+            3,
+            &[
+                10.0, -10.0, 0.0, //
+                0.0, 10.0, -0.2, //
+                0.0, 0.0, 1.0, //
+                0.0, 3.0, 0.0,
+            ],
+        )
+    }
+
+    #[test]
+    fn matvec() {
+        let dense = example();
+        let csr = CsrMatrix::from_dense(&dense);
+        let csc = CscMatrix::from_dense(&dense);
+        assert_eq!(csr.nnz(), 6);
+        assert_eq!(csc.nnz(), 6);
+        assert_eq!(csr.to_dense(), dense);
+        assert_eq!(csc.to_dense(), dense);
+        assert_eq!(csc.to_csr().to_dense(), dense);
+        assert_eq!(csr.transpose().to_dense(), dense.transpose());
+        let x = na::DVector::from_column_slice(&[1.0, 2.0, 3.0]);
+        let y = na::DVector::from_column_slice(&[1.0, -1.0, 0.5, 2.0]);
+        assert_eq!(csr.mul_vec(&x), &dense * &x);
+        assert_eq!(csc.mul_vec(&x), &dense * &x);
+        assert_eq!(csr.tr_mul_vec(&y), dense.transpose() * &y);
+        assert_eq!(csc.tr_mul_vec(&y), dense.transpose() * &y);
+        let triplets = CsrMatrix::from_triplets(2, 2, &[(1, 0, 1.0), (0, 1, 2.0), (1, 0, 3.0)]);
+        assert_eq!(
+            triplets.to_dense(),
+            na::DMatrix::from_row_slice(2, 2, &[0.0, 2.0, 4.0, 0.0])
+        );
+    }
+
+    #[test]
+    fn iterative_solvers() {
+        let dense = example();
+        let b = na::DVector::from_column_slice(&[0.01, 0.01, 50.0, 1.0]);
+        let expected = cod_least_squares(
+            &dense,
+            &na::DMatrix::from_column_slice(4, 1, b.as_slice()),
+            1e-12,
+        );
+        let csr = CsrMatrix::from_dense(&dense);
+        for res in [cgls(&csr, &b, 1e-12, 100), lsqr(&csr, &b, 1e-12, 100)] {
+            assert!(res.converged);
+            assert!((&res.x - expected.beta.column(0)).amax() <= 1e-8);
+            assert!((res.residual_norm - (&dense * &res.x - &b).norm()).abs() <= 1e-8);
+        }
+    }
+
+    #[test]
+    fn rank_deficient_minimum_norm() {
+        // fisher and hunter both make meat
+        let dense = na::DMatrix::from_row_slice(2, 3, &[1.0, 1.0, -1.0, 0.0, 0.0, 1.0]);
+        let b = na::DVector::from_column_slice(&[0.0, 10.0]);
+        let csc = CscMatrix::from_dense(&dense);
+        for res in [cgls(&csc, &b, 1e-12, 100), lsqr(&csc, &b, 1e-12, 100)] {
+            assert!(res.converged);
+            assert!((&res.x - na::DVector::from_column_slice(&[5.0, 5.0, 10.0])).amax() <= 1e-8);
+        }
+    }
+}
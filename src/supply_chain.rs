@@ -0,0 +1,210 @@
+// Random but plausible economies for scaling studies: goods in layers, raw resources at the
+// bottom gathered without inputs, every higher good processed from one to three goods below it
+
+use crate::economy::Industry;
+use rand::{rngs::StdRng, RngExt, SeedableRng};
+
+/// Shape of the generated economies
+#[derive(Clone, Debug)]
+pub struct Generator {
+    pub goods: usize,
+    // goods are spread evenly over the layers, layer 0 are the raw resources
+    pub layers: usize,
+    // chance that a processor also needs a little of a good made from its own output, which
+    // closes a cycle
+    pub cycles: f64,
+    // chance that a good gets a second producer with its own recipe
+    pub duplicates: f64,
+}
+
+impl Default for Generator {
+    fn default() -> Self {
+        Generator {
+            goods: 51,
+            layers: 5,
+            cycles: 0.05,
+            duplicates: 0.1,
+        }
+    }
+}
+
+/// A generated economy: labor `l` uses `industry(l).inputs` to make `industry(l).outputs`
+pub struct SupplyChain {
+    pub goods: Vec<String>,
+    pub labors: Vec<String>,
+    // layer of each good
+    pub layer: Vec<usize>,
+    inputs: Vec<Vec<(usize, f32)>>,
+    outputs: Vec<Vec<(usize, f32)>>,
+}
+
+impl Generator {
+    /// The same seed gives the same economy
+    pub fn generate(&self, seed: u64) -> SupplyChain {
+        assert!(self.goods > 0 && self.layers > 0, "empty economy");
+        let layers = self.layers.min(self.goods);
+        let mut rng = StdRng::seed_from_u64(seed);
+        let mut chain = SupplyChain {
+            goods: (0..self.goods).map(|g| format!("Good{}", g)).collect(),
+            labors: Vec::new(),
+            layer: (0..self.goods).map(|g| g * layers / self.goods).collect(),
+            inputs: Vec::new(),
+            outputs: Vec::new(),
+        };
+        for g in 0..self.goods {
+            // the goods below the layer of g are 0..below
+            let below = chain.layer.partition_point(|&l| l < chain.layer[g]);
+            let producers = if rng.random_bool(self.duplicates) {
+                2
+            } else {
+                1
+            };
+            for p in 0..producers {
+                let mut inputs: Vec<(usize, f32)> = Vec::new();
+                if below > 0 {
+                    for _ in 0..rng.random_range(1..=3usize.min(below)) {
+                        let input = rng.random_range(0..below);
+                        if inputs.iter().all(|&(i, _)| i != input) {
+                            inputs.push((input, rng.random_range(0.1..5.0)));
+                        }
+                    }
+                }
+                let kind = if below == 0 { "Gatherer" } else { "Maker" };
+                chain.labors.push(match p {
+                    0 => format!("{}{}", kind, g),
+                    _ => format!("{}{}_{}", kind, g, p),
+                });
+                chain.inputs.push(inputs);
+                chain.outputs.push(vec![(g, rng.random_range(1.0..10.0))]);
+            }
+        }
+
+        // feedback: a processor needs a little of what one of its customers makes
+        for l in 0..chain.labors.len() {
+            if chain.inputs[l].is_empty() || !rng.random_bool(self.cycles) {
+                continue;
+            }
+            let made = chain.outputs[l][0].0;
+            let customers: Vec<usize> = (0..chain.labors.len())
+                .filter(|&c| chain.inputs[c].iter().any(|&(i, _)| i == made))
+                .map(|c| chain.outputs[c][0].0)
+                .filter(|&g| chain.inputs[l].iter().all(|&(i, _)| i != g))
+                .collect();
+            if !customers.is_empty() {
+                let good = customers[rng.random_range(0..customers.len())];
+                chain.inputs[l].push((good, rng.random_range(0.01..0.1)));
+            }
+        }
+        chain
+    }
+}
+
+impl SupplyChain {
+    pub fn industry(&self, labor: usize) -> Industry<'_, usize> {
+        Industry {
+            inputs: &self.inputs[labor],
+            outputs: &self.outputs[labor],
+        }
+    }
+
+    /// Net output of each good (rows) per laborer of each labor (columns), as
+    /// `Economy::recipe_matrix`
+    pub fn recipe_matrix(&self) -> na::DMatrix<f32> {
+        let mut x = na::DMatrix::zeros(self.goods.len(), self.labors.len());
+        for l in 0..self.labors.len() {
+            let industry = self.industry(l);
+            for &(good, amount) in industry.outputs {
+                x[(good, l)] += amount;
+            }
+            for &(good, amount) in industry.inputs {
+                x[(good, l)] -= amount;
+            }
+        }
+        x
+    }
+
+    /// `recipe_matrix` with static size, panics unless the economy has M goods and N labors
+    pub fn smatrix<const M: usize, const N: usize>(&self) -> na::SMatrix<f32, M, N> {
+        assert_eq!((self.goods.len(), self.labors.len()), (M, N), "wrong size");
+        let x = self.recipe_matrix();
+        na::SMatrix::from_fn(|r, c| x[(r, c)])
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Generator;
+    use crate::recipe_graph::{Cycle, RecipeGraph};
+
+    #[test]
+    fn layered_economies() {
+        let generator = Generator {
+            goods: 60,
+            layers: 4,
+            cycles: 0.0,
+            duplicates: 0.0,
+        };
+        let chain = generator.generate(7);
+        assert_eq!(chain.recipe_matrix(), generator.generate(7).recipe_matrix());
+        assert_eq!(chain.labors.len(), 60);
+        for l in 0..chain.labors.len() {
+            let industry = chain.industry(l);
+            let (made, _) = industry.outputs[0];
+            let inputs = industry.inputs.len();
+            assert!(if chain.layer[made] == 0 {
+                inputs == 0
+            } else {
+                (1..=3).contains(&inputs)
+            });
+            assert!(industry
+                .inputs
+                .iter()
+                .all(|&(g, _)| chain.layer[g] < chain.layer[made]));
+        }
+        let x = chain.recipe_matrix();
+        let graph = RecipeGraph::new(60, 60, |r, c| x[(r, c)], |_| 1.0_f32, 0.0);
+        assert!(graph.cycles().is_empty());
+        assert!(graph.components().iter().all(|c| c.len() == 1));
+
+        let chain = Generator {
+            cycles: 1.0,
+            duplicates: 1.0,
+            ..generator
+        }
+        .generate(7);
+        assert_eq!(chain.labors.len(), 120);
+        let x = chain.smatrix::<60, 120>();
+        let graph = RecipeGraph::new(60, 120, |r, c| x[(r, c)], |_| 1.0_f32, 0.0);
+        assert!(!graph.cycles().is_empty());
+        assert!(graph.propagate(120).non_productive.is_empty());
+    }
+
+    #[test]
+    fn parallel_propagation() {
+        let chain = Generator {
+            goods: 120,
+            layers: 6,
+            cycles: 1.0,
+            duplicates: 1.0,
+        }
+        .generate(11);
+        let x = chain.recipe_matrix();
+        let (goods, labors) = x.shape();
+        let graph = RecipeGraph::new(goods, labors, |r, c| x[(r, c)], |_| 1.0_f32, 0.001);
+        assert!(!graph.cycles().is_empty());
+        assert!(graph.levels(&graph.components()).len() > 2);
+        let serial = graph.propagate(labors);
+        let parallel = graph.propagate_parallel(labors);
+        let bits = |effort: &[Vec<f32>]| -> Vec<u32> {
+            effort.iter().flatten().map(|v| v.to_bits()).collect()
+        };
+        assert_eq!(bits(&parallel.effort), bits(&serial.effort));
+        let goods = |cycles: &[Cycle<f32>]| -> Vec<Vec<usize>> {
+            cycles.iter().map(|cycle| cycle.goods.clone()).collect()
+        };
+        assert_eq!(
+            goods(&parallel.non_productive),
+            goods(&serial.non_productive)
+        );
+    }
+}